@@ -0,0 +1,17 @@
+; Plays an XO-CHIP audio pattern for half a second at a time, raising its
+; pitch each time.
+        LD I, pattern
+        AUDIO
+        LD V0, 16
+loop:   PITCH V0
+        LD V1, 30
+        LD ST, V1
+        LD DT, V1
+wait:   LD V1, DT
+        SE V1, 0
+        JP wait
+        ADD V0, 16
+        JP loop
+pattern:
+        DB 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00
+        DB 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0
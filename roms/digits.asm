@@ -0,0 +1,15 @@
+; Draws the 16 digits of the font in two rows and stops.
+        LD V0, 0
+        LD V1, 2
+        LD V2, 6
+next:   LD F, V0
+        DRW V1, V2, 5
+        ADD V0, 1
+        ADD V1, 8
+        SE V0, 8
+        JP wrap
+        LD V1, 2
+        LD V2, 18
+wrap:   SE V0, 16
+        JP next
+stop:   JP stop
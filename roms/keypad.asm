@@ -0,0 +1,35 @@
+; Shows the hex digit of every keypad key held down, in two rows of eight,
+; redrawn each frame. Handy for checking a key layout.
+loop:   CLS
+        LD V0, 0
+next:   SKNP V0
+        CALL digit
+        ADD V0, 1
+        SE V0, 16
+        JP next
+        LD V3, 1
+        LD DT, V3
+wait:   LD V3, DT
+        SE V3, 0
+        JP wait
+        JP loop
+
+; Draws the digit of key V0 in its place of the grid.
+digit:  LD V1, V0
+        LD V2, 7
+        AND V1, V2
+        SHL V1
+        SHL V1
+        SHL V1
+        ADD V1, 2
+        LD V2, V0
+        SHR V2
+        SHR V2
+        SHR V2
+        SHL V2
+        SHL V2
+        SHL V2
+        ADD V2, 10
+        LD F, V0
+        DRW V1, V2, 5
+        RET
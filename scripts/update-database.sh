@@ -0,0 +1,12 @@
+#!/bin/sh
+# Replaces data/programs.json with the programs.json of the community CHIP-8
+# database, which is compiled into the emulator. The entries of the demo ROMs
+# in roms/ are kept in data/demos.json.
+set -eu
+
+url=https://raw.githubusercontent.com/chip-8/chip-8-database/master/database/programs.json
+target="$(dirname "$0")/../data/programs.json"
+
+curl -fsSL "$url" -o "$target.tmp"
+mv "$target.tmp" "$target"
+echo "Updated $target"
@@ -0,0 +1,169 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::{opcode::Opcode, processor::emulated, quirks::Platform};
+
+/// The instruction set extension an instruction belongs to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Family {
+    /// Instructions of the original interpreter, all emulated.
+    Classic,
+    /// 0NNN calls into 1802 machine code, which cannot be emulated.
+    MachineCode,
+    /// SUPER-CHIP additions such as 00FF and DXY0.
+    Schip,
+    /// XO-CHIP additions such as F000 and 5XY2.
+    Xochip,
+    /// Words that are no instruction on any platform.
+    Invalid,
+}
+
+impl fmt::Display for Family {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Family::Classic => "classic",
+            Family::MachineCode => "machine code",
+            Family::Schip => "SCHIP",
+            Family::Xochip => "XO-CHIP",
+            Family::Invalid => "invalid",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+pub fn family(word: u16) -> Family {
+    let opcode: Opcode = word.into();
+
+    match (opcode.category, opcode.x, opcode.y, opcode.n) {
+        (0x0, 0x0, 0xE, 0x0) | (0x0, 0x0, 0xE, 0xE) => Family::Classic,
+        (0x0, 0x0, 0xC, _) | (0x0, 0x0, 0xF, 0xB..=0xF) => Family::Schip,
+        (0x0, 0x0, 0xD, _) => Family::Xochip,
+        (0x0, ..) => Family::MachineCode,
+        (0x5, _, _, 0x0) => Family::Classic,
+        (0x5, _, _, 0x2 | 0x3) => Family::Xochip,
+        (0x5, ..) => Family::Invalid,
+        (0x8, _, _, 0x0..=0x7 | 0xE) => Family::Classic,
+        (0x8, ..) => Family::Invalid,
+        (0x9, _, _, 0x0) => Family::Classic,
+        (0x9, ..) => Family::Invalid,
+        (0xD, _, _, 0x0) => Family::Schip,
+        (0xE, _, 0x9, 0xE) | (0xE, _, 0xA, 0x1) => Family::Classic,
+        (0xE, ..) => Family::Invalid,
+        (0xF, 0x0, 0x0, 0x0) | (0xF, _, 0x0, 0x1) | (0xF, 0x0, 0x0, 0x2) => Family::Xochip,
+        (0xF, _, 0x3, 0xA) => Family::Xochip,
+        (0xF, _, 0x3, 0x0) | (0xF, _, 0x7, 0x5) | (0xF, _, 0x8, 0x5) => Family::Schip,
+        (0xF, _, 0x0, 0x7 | 0xA)
+        | (0xF, _, 0x1, 0x5 | 0x8 | 0xE)
+        | (0xF, _, 0x2, 0x9)
+        | (0xF, _, 0x3, 0x3)
+        | (0xF, _, 0x5 | 0x6, 0x5) => Family::Classic,
+        (0xF, ..) => Family::Invalid,
+        _ => Family::Classic,
+    }
+}
+
+// XO-CHIP's F000 NNNN is the only instruction that is four bytes long.
+fn length(word: u16) -> u16 {
+    if word == 0xF000 {
+        4
+    } else {
+        2
+    }
+}
+
+/// The result of following every path through a ROM from its entry point.
+pub struct Analysis {
+    /// Number of instructions reached, by family.
+    pub families: BTreeMap<Family, usize>,
+    /// Every reached instruction that `Chip8` cannot emulate, by address.
+    pub unsupported: BTreeMap<u16, u16>,
+    /// Bytes of the ROM that no path reaches, usually sprites and data.
+    pub unreached_bytes: usize,
+    /// Whether a BNNN jump was found, whose target depends on a register and
+    /// so may lead to code the scan did not see.
+    pub computed_jumps: bool,
+}
+
+impl Analysis {
+    /// The platform the instructions seen point at.
+    pub fn platform(&self) -> Platform {
+        if self.families.contains_key(&Family::Xochip) {
+            Platform::Xochip
+        } else if self.families.contains_key(&Family::Schip) {
+            Platform::Schip
+        } else {
+            Platform::Chip8
+        }
+    }
+}
+
+/// Follows jumps, calls and skips from `origin`, the address `rom` is loaded
+/// at, classifying each instruction it reaches.
+pub fn analyze(rom: &[u8], origin: u16) -> Analysis {
+    let mut visited = vec![false; rom.len()];
+    let mut pending = vec![origin];
+    let mut analysis = Analysis {
+        families: BTreeMap::new(),
+        unsupported: BTreeMap::new(),
+        unreached_bytes: 0,
+        computed_jumps: false,
+    };
+
+    let word_at = |address: u16| {
+        let offset = address.checked_sub(origin)? as usize;
+        Some(u16::from_be_bytes([
+            *rom.get(offset)?,
+            *rom.get(offset + 1)?,
+        ]))
+    };
+
+    while let Some(address) = pending.pop() {
+        let offset = (address - origin) as usize;
+        let Some(word) = word_at(address) else {
+            continue;
+        };
+        if visited[offset] {
+            continue;
+        }
+
+        let size = length(word);
+        for visited in visited.iter_mut().skip(offset).take(size as usize) {
+            *visited = true;
+        }
+
+        let family = family(word);
+        *analysis.families.entry(family).or_default() += 1;
+        if !emulated(word) {
+            analysis.unsupported.insert(address, word);
+        }
+
+        let opcode: Opcode = word.into();
+        let next = address.wrapping_add(size);
+        let mut follow = |target: u16| {
+            if target >= origin {
+                pending.push(target);
+            }
+        };
+
+        match (opcode.category, word) {
+            (_, 0x00EE) | (_, 0x00FD) => {}
+            (0x1, _) => follow(opcode.nnn()),
+            (0x2, _) => {
+                follow(opcode.nnn());
+                follow(next);
+            }
+            (0xB, _) => analysis.computed_jumps = true,
+            (0x3 | 0x4 | 0x5 | 0x9, _) | (0xE, _) => {
+                follow(next);
+                let skipped = word_at(next).map(length).unwrap_or(2);
+                follow(next.wrapping_add(skipped));
+            }
+            _ => follow(next),
+        }
+    }
+
+    analysis.unreached_bytes = visited.iter().filter(|visited| !**visited).count();
+
+    analysis
+}
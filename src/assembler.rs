@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+/// An error in the assembly source, with the 1-based line it occurred on.
+#[derive(Debug)]
+pub struct AssembleError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AssembleError {}
+
+enum Operand {
+    Register(u16),
+    I,
+    IndirectI,
+    DelayTimer,
+    SoundTimer,
+    Key,
+    Font,
+    BigFont,
+    Bcd,
+    Value(u16),
+}
+
+struct Statement<'a> {
+    line: usize,
+    mnemonic: String,
+    operands: Vec<&'a str>,
+}
+
+/// Assembles source in the syntax produced by the disassembler. Besides the
+/// instructions it understands `label:` definitions, `DB`/`DW` data
+/// directives and `;` comments. `origin` is the address the program is
+/// loaded at, which labels are resolved against.
+pub fn assemble(source: &str, origin: u16) -> Result<Vec<u8>, AssembleError> {
+    let mut labels = HashMap::new();
+    let mut statements = vec![];
+    let mut address = origin as usize;
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let mut text = line.split(';').next().unwrap_or("").trim();
+
+        while let Some((label, rest)) = split_label(text) {
+            if labels.insert(label.to_string(), address as u16).is_some() {
+                return Err(AssembleError {
+                    line: line_number,
+                    message: format!("label '{}' is defined more than once", label),
+                });
+            }
+            text = rest;
+        }
+
+        if text.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let operands: Vec<&str> = if rest.trim().is_empty() {
+            vec![]
+        } else {
+            rest.split(',').map(str::trim).collect()
+        };
+        let mnemonic = mnemonic.to_ascii_uppercase();
+
+        address += match mnemonic.as_str() {
+            "DB" => operands.len(),
+            "DW" => operands.len() * 2,
+            _ => 2,
+        };
+
+        if address > 0x10000 {
+            return Err(AssembleError {
+                line: line_number,
+                message: "program does not fit in the address space".to_string(),
+            });
+        }
+
+        statements.push(Statement {
+            line: line_number,
+            mnemonic,
+            operands,
+        });
+    }
+
+    let mut output = vec![];
+    for statement in &statements {
+        encode(statement, &labels, &mut output).map_err(|message| AssembleError {
+            line: statement.line,
+            message,
+        })?;
+    }
+
+    Ok(output)
+}
+
+fn split_label(text: &str) -> Option<(&str, &str)> {
+    let (label, rest) = text.split_once(':')?;
+    let label = label.trim();
+
+    if label.is_empty() || !label.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+
+    Some((label, rest.trim()))
+}
+
+fn parse_number(text: &str) -> Option<u32> {
+    let text = text.trim();
+
+    if let Some(hex) = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .or_else(|| text.strip_prefix('#'))
+    {
+        u32::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = text.strip_prefix("0b").or_else(|| text.strip_prefix("0B")) {
+        u32::from_str_radix(binary, 2).ok()
+    } else {
+        text.parse().ok()
+    }
+}
+
+fn parse_operand(text: &str, labels: &HashMap<String, u16>) -> Result<Operand, String> {
+    let upper = text.to_ascii_uppercase();
+
+    let operand = match upper.as_str() {
+        "I" => Operand::I,
+        "[I]" => Operand::IndirectI,
+        "DT" => Operand::DelayTimer,
+        "ST" => Operand::SoundTimer,
+        "K" => Operand::Key,
+        "F" => Operand::Font,
+        "HF" => Operand::BigFont,
+        "B" => Operand::Bcd,
+        _ => {
+            if let Some(register) = upper
+                .strip_prefix('V')
+                .filter(|r| r.len() == 1)
+                .and_then(|r| u16::from_str_radix(r, 16).ok())
+            {
+                Operand::Register(register)
+            } else if let Some(value) = parse_number(text) {
+                if value > 0xFFFF {
+                    return Err(format!("value '{}' is larger than 16 bits", text));
+                }
+                Operand::Value(value as u16)
+            } else if let Some(address) = labels.get(text) {
+                Operand::Value(*address)
+            } else {
+                return Err(format!("unknown operand or label '{}'", text));
+            }
+        }
+    };
+
+    Ok(operand)
+}
+
+fn limit(value: u16, max: u16, what: &str) -> Result<u16, String> {
+    if value > max {
+        return Err(format!("{} {:#X} does not fit in {:#X}", what, value, max));
+    }
+
+    Ok(value)
+}
+
+fn encode(
+    statement: &Statement,
+    labels: &HashMap<String, u16>,
+    output: &mut Vec<u8>,
+) -> Result<(), String> {
+    let operands = statement
+        .operands
+        .iter()
+        .map(|o| parse_operand(o, labels))
+        .collect::<Result<Vec<Operand>, String>>()?;
+
+    let address = |value: u16| limit(value, 0xFFF, "address");
+    let byte = |value: u16| limit(value, 0xFF, "byte");
+
+    let word = match (statement.mnemonic.as_str(), operands.as_slice()) {
+        ("DB", values) => {
+            for value in values {
+                match value {
+                    Operand::Value(value) => output.push(byte(*value)? as u8),
+                    _ => return Err("DB expects numbers".to_string()),
+                }
+            }
+            return Ok(());
+        }
+        ("DW", values) => {
+            for value in values {
+                match value {
+                    Operand::Value(value) => output.extend_from_slice(&value.to_be_bytes()),
+                    _ => return Err("DW expects numbers".to_string()),
+                }
+            }
+            return Ok(());
+        }
+        ("CLS", []) => 0x00E0,
+        ("RET", []) => 0x00EE,
+        ("SYS", [Operand::Value(a)]) => address(*a)?,
+        ("JP", [Operand::Value(a)]) => 0x1000 | address(*a)?,
+        ("JP", [Operand::Register(0), Operand::Value(a)]) => 0xB000 | address(*a)?,
+        ("CALL", [Operand::Value(a)]) => 0x2000 | address(*a)?,
+        ("SE", [Operand::Register(x), Operand::Value(k)]) => 0x3000 | x << 8 | byte(*k)?,
+        ("SNE", [Operand::Register(x), Operand::Value(k)]) => 0x4000 | x << 8 | byte(*k)?,
+        ("SE", [Operand::Register(x), Operand::Register(y)]) => 0x5000 | x << 8 | y << 4,
+        ("LD", [Operand::Register(x), Operand::Value(k)]) => 0x6000 | x << 8 | byte(*k)?,
+        ("ADD", [Operand::Register(x), Operand::Value(k)]) => 0x7000 | x << 8 | byte(*k)?,
+        ("LD", [Operand::Register(x), Operand::Register(y)]) => 0x8000 | x << 8 | y << 4,
+        ("OR", [Operand::Register(x), Operand::Register(y)]) => 0x8001 | x << 8 | y << 4,
+        ("AND", [Operand::Register(x), Operand::Register(y)]) => 0x8002 | x << 8 | y << 4,
+        ("XOR", [Operand::Register(x), Operand::Register(y)]) => 0x8003 | x << 8 | y << 4,
+        ("ADD", [Operand::Register(x), Operand::Register(y)]) => 0x8004 | x << 8 | y << 4,
+        ("SUB", [Operand::Register(x), Operand::Register(y)]) => 0x8005 | x << 8 | y << 4,
+        ("SHR", [Operand::Register(x)]) => 0x8006 | x << 8 | x << 4,
+        ("SHR", [Operand::Register(x), Operand::Register(y)]) => 0x8006 | x << 8 | y << 4,
+        ("SUBN", [Operand::Register(x), Operand::Register(y)]) => 0x8007 | x << 8 | y << 4,
+        ("SHL", [Operand::Register(x)]) => 0x800E | x << 8 | x << 4,
+        ("SHL", [Operand::Register(x), Operand::Register(y)]) => 0x800E | x << 8 | y << 4,
+        ("SNE", [Operand::Register(x), Operand::Register(y)]) => 0x9000 | x << 8 | y << 4,
+        ("LD", [Operand::I, Operand::Value(a)]) => 0xA000 | address(*a)?,
+        ("RND", [Operand::Register(x), Operand::Value(k)]) => 0xC000 | x << 8 | byte(*k)?,
+        ("DRW", [Operand::Register(x), Operand::Register(y), Operand::Value(n)]) => {
+            0xD000 | x << 8 | y << 4 | limit(*n, 0xF, "sprite height")?
+        }
+        ("SKP", [Operand::Register(x)]) => 0xE09E | x << 8,
+        ("SKNP", [Operand::Register(x)]) => 0xE0A1 | x << 8,
+        ("LD", [Operand::Register(x), Operand::DelayTimer]) => 0xF007 | x << 8,
+        ("LD", [Operand::Register(x), Operand::Key]) => 0xF00A | x << 8,
+        ("LD", [Operand::DelayTimer, Operand::Register(x)]) => 0xF015 | x << 8,
+        ("LD", [Operand::SoundTimer, Operand::Register(x)]) => 0xF018 | x << 8,
+        ("ADD", [Operand::I, Operand::Register(x)]) => 0xF01E | x << 8,
+        ("LD", [Operand::Font, Operand::Register(x)]) => 0xF029 | x << 8,
+        ("LD", [Operand::BigFont, Operand::Register(x)]) => 0xF030 | x << 8,
+        ("LD", [Operand::Bcd, Operand::Register(x)]) => 0xF033 | x << 8,
+        ("AUDIO", []) => 0xF002,
+        ("PITCH", [Operand::Register(x)]) => 0xF03A | x << 8,
+        ("LD", [Operand::IndirectI, Operand::Register(x)]) => 0xF055 | x << 8,
+        ("LD", [Operand::Register(x), Operand::IndirectI]) => 0xF065 | x << 8,
+        _ => {
+            return Err(format!(
+                "invalid instruction '{} {}'",
+                statement.mnemonic,
+                statement.operands.join(", ")
+            ))
+        }
+    };
+
+    output.extend_from_slice(&word.to_be_bytes());
+
+    Ok(())
+}
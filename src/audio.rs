@@ -0,0 +1,299 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+
+pub const SAMPLE_RATE: u32 = 44_100;
+pub const DEFAULT_TONE: u16 = 440;
+pub const MIN_TONE: u16 = 20;
+pub const MAX_TONE: u16 = 20_000;
+
+/// The XO-CHIP pitch a pattern plays at until FX3A sets another, 4000 Hz.
+pub const DEFAULT_PITCH: u8 = 64;
+pub const DEFAULT_VOLUME: u8 = 25;
+
+// 44100 Hz divides evenly into 60 Hz frames.
+const SAMPLES_PER_FRAME: usize = (SAMPLE_RATE / 60) as usize;
+
+/// Where the buzzer goes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SoundSink {
+    /// No sound.
+    Off,
+    /// The terminal bell, rung each time the buzzer starts.
+    Bell,
+    /// A WAV file, 16-bit mono at 44100 Hz.
+    Wav,
+    /// Raw signed 16-bit little-endian mono samples at 44100 Hz, e.g. for
+    /// `aplay -f S16_LE -r 44100`.
+    Pcm,
+}
+
+/// An XO-CHIP audio pattern: 128 one-bit samples, most significant bit
+/// first, played in a loop at a rate set by the pitch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pattern {
+    pub bits: [u8; 16],
+    pub pitch: u8,
+}
+
+impl Pattern {
+    /// Bits played per second, `4000 * 2^((pitch - 64) / 48)`.
+    pub fn rate(&self) -> f64 {
+        4000.0 * 2_f64.powf((self.pitch as f64 - 64.0) / 48.0)
+    }
+
+    fn bit(&self, index: usize) -> bool {
+        self.bits[index / 8] & (0x80 >> (index % 8)) != 0
+    }
+}
+
+/// Makes the square wave the buzzer sounds, or plays the XO-CHIP pattern
+/// loaded instead, one frame of samples at a time.
+struct Buzzer {
+    tone: u16,
+    amplitude: i16,
+    // Position in the current period of the square wave, in samples times
+    // the tone.
+    phase: u32,
+    // Position in the pattern, in bits.
+    position: f64,
+    samples: Vec<i16>,
+}
+
+impl Buzzer {
+    /// A buzzer of `tone` Hz at `volume` percent of full scale.
+    fn new(tone: u16, volume: u8) -> Buzzer {
+        Buzzer {
+            tone,
+            amplitude: (i16::MAX as i32 * volume.min(100) as i32 / 100) as i16,
+            phase: 0,
+            position: 0.0,
+            samples: Vec::with_capacity(SAMPLES_PER_FRAME),
+        }
+    }
+
+    /// The samples of one 60 Hz frame: `pattern` if there is one, else the
+    /// square wave, when `on` and silence otherwise. Sound starts over after
+    /// a silence.
+    fn frame(&mut self, on: bool, pattern: Option<Pattern>) -> &[i16] {
+        self.samples.clear();
+
+        if !on {
+            self.phase = 0;
+            self.position = 0.0;
+            self.samples.resize(SAMPLES_PER_FRAME, 0);
+            return &self.samples;
+        }
+
+        let step = pattern.map_or(0.0, |pattern| pattern.rate() / SAMPLE_RATE as f64);
+        for _ in 0..SAMPLES_PER_FRAME {
+            let high = match pattern {
+                Some(pattern) => {
+                    let high = pattern.bit(self.position as usize);
+                    self.position = (self.position + step) % 128.0;
+                    high
+                }
+                None => {
+                    let high = self.phase < SAMPLE_RATE / 2;
+                    self.phase = (self.phase + self.tone as u32) % SAMPLE_RATE;
+                    high
+                }
+            };
+            self.samples.push(if high {
+                self.amplitude
+            } else {
+                -self.amplitude
+            });
+        }
+
+        &self.samples
+    }
+}
+
+/// Plays the buzzer frame by frame.
+trait Sink: Send {
+    /// Plays one frame of `samples`, in which the buzzer is `on` or not.
+    fn play(&mut self, samples: &[i16], on: bool) -> Result<(), String>;
+
+    /// Ends the sound, e.g. by completing a file.
+    fn finish(self: Box<Self>) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Has the terminal bell rung by `ring`, for sinks that ring it.
+    fn ring_with(&mut self, _ring: Box<dyn FnMut() + Send>) {}
+}
+
+/// The buzzer and where it is played.
+pub struct Audio {
+    buzzer: Buzzer,
+    sink: Box<dyn Sink>,
+}
+
+impl Audio {
+    /// Opens the sink for `kind`, or returns `None` for no sound. WAV files
+    /// go to `path`, raw samples to `path` or standard output.
+    pub fn open(
+        kind: SoundSink,
+        path: Option<&Path>,
+        tone: u16,
+        volume: u8,
+    ) -> Result<Option<Audio>, String> {
+        Ok(sink(kind, path)?.map(|sink| Audio {
+            buzzer: Buzzer::new(tone, volume),
+            sink,
+        }))
+    }
+
+    /// Plays one 60 Hz frame, in which the buzzer is `on` or not, playing
+    /// `pattern` if the program loaded one.
+    pub fn frame(&mut self, on: bool, pattern: Option<Pattern>) -> Result<(), String> {
+        let samples = self.buzzer.frame(on, pattern);
+        self.sink.play(samples, on)
+    }
+
+    pub fn finish(self) -> Result<(), String> {
+        self.sink.finish()
+    }
+
+    /// Has the terminal bell rung by `ring` instead of written to standard
+    /// output, for when another thread draws the terminal.
+    pub fn ring_bell_with(&mut self, ring: Box<dyn FnMut() + Send>) {
+        self.sink.ring_with(ring);
+    }
+}
+
+fn sink(kind: SoundSink, path: Option<&Path>) -> Result<Option<Box<dyn Sink>>, String> {
+    Ok(match (kind, path) {
+        (SoundSink::Off, _) => None,
+        (SoundSink::Bell, _) => Some(Box::new(Bell {
+            ringing: false,
+            ring: None,
+        })),
+        (SoundSink::Wav, Some(path)) => Some(Box::new(Wav::create(path)?)),
+        (SoundSink::Wav, None) => return Err("--sound wav needs a --sound-file".into()),
+        (SoundSink::Pcm, Some(path)) => Some(Box::new(Pcm {
+            out: Box::new(BufWriter::new(
+                File::create(path)
+                    .map_err(|e| format!("Could not write {}: {}", path.display(), e))?,
+            )),
+            name: path.display().to_string(),
+        })),
+        (SoundSink::Pcm, None) => Some(Box::new(Pcm {
+            out: Box::new(io::stdout()),
+            name: "standard output".into(),
+        })),
+    })
+}
+
+struct Bell {
+    ringing: bool,
+    // Rings the bell in place of writing it to standard output.
+    ring: Option<Box<dyn FnMut() + Send>>,
+}
+
+impl Sink for Bell {
+    fn play(&mut self, _samples: &[i16], on: bool) -> Result<(), String> {
+        let start = on && !self.ringing;
+        self.ringing = on;
+
+        match &mut self.ring {
+            Some(ring) if start => ring(),
+            None if start => {
+                let mut out = io::stdout();
+                out.write_all(b"\x07")
+                    .and_then(|()| out.flush())
+                    .map_err(|e| format!("Could not ring the bell: {}", e))?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn ring_with(&mut self, ring: Box<dyn FnMut() + Send>) {
+        self.ring = Some(ring);
+    }
+}
+
+struct Wav {
+    out: BufWriter<File>,
+    path: PathBuf,
+    bytes: u32,
+}
+
+impl Wav {
+    fn create(path: &Path) -> Result<Wav, String> {
+        let mut wav = Wav {
+            out: BufWriter::new(
+                File::create(path)
+                    .map_err(|e| format!("Could not write {}: {}", path.display(), e))?,
+            ),
+            path: path.to_path_buf(),
+            bytes: 0,
+        };
+        // The sizes are filled in by `finish`.
+        wav.write_header().map_err(|e| wav.error(e))?;
+
+        Ok(wav)
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let out = &mut self.out;
+        out.write_all(b"RIFF")?;
+        out.write_all(&self.bytes.saturating_add(36).to_le_bytes())?;
+        out.write_all(b"WAVEfmt ")?;
+        out.write_all(&16_u32.to_le_bytes())?;
+        // PCM, one channel, the sample rate, bytes per second, bytes per
+        // sample and bits per sample.
+        out.write_all(&1_u16.to_le_bytes())?;
+        out.write_all(&1_u16.to_le_bytes())?;
+        out.write_all(&SAMPLE_RATE.to_le_bytes())?;
+        out.write_all(&(SAMPLE_RATE * 2).to_le_bytes())?;
+        out.write_all(&2_u16.to_le_bytes())?;
+        out.write_all(&16_u16.to_le_bytes())?;
+        out.write_all(b"data")?;
+        out.write_all(&self.bytes.to_le_bytes())
+    }
+
+    fn error(&self, e: io::Error) -> String {
+        format!("Could not write {}: {}", self.path.display(), e)
+    }
+}
+
+impl Sink for Wav {
+    fn play(&mut self, samples: &[i16], _on: bool) -> Result<(), String> {
+        write_samples(&mut self.out, samples).map_err(|e| self.error(e))?;
+        self.bytes = self.bytes.saturating_add(samples.len() as u32 * 2);
+
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), String> {
+        self.out
+            .seek(SeekFrom::Start(0))
+            .and_then(|_| self.write_header())
+            .and_then(|()| self.out.flush())
+            .map_err(|e| self.error(e))
+    }
+}
+
+struct Pcm {
+    out: Box<dyn Write + Send>,
+    name: String,
+}
+
+impl Sink for Pcm {
+    fn play(&mut self, samples: &[i16], _on: bool) -> Result<(), String> {
+        write_samples(&mut self.out, samples)
+            .and_then(|()| self.out.flush())
+            .map_err(|e| format!("Could not write to {}: {}", self.name, e))
+    }
+}
+
+fn write_samples(out: &mut impl Write, samples: &[i16]) -> io::Result<()> {
+    let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+    out.write_all(&bytes)
+}
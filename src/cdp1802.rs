@@ -0,0 +1,499 @@
+/// What the CPU is wired to: memory, the I/O ports of OUT and INP and the
+/// four external flag lines.
+pub trait Bus {
+    fn read(&mut self, address: u16) -> u8;
+    fn write(&mut self, address: u16, value: u8);
+    /// OUT 1 to OUT 7 put a byte from memory on the bus for `port`.
+    fn output(&mut self, port: u8, value: u8);
+    /// INP 1 to INP 7 read a byte from `port` into memory and D.
+    fn input(&mut self, port: u8) -> u8;
+    /// Whether flag line EF1 to EF4 is asserted.
+    fn flag(&self, line: u8) -> bool;
+}
+
+/// An RCA CDP1802 COSMAC microprocessor. Timing is counted in machine
+/// cycles of 8 clock cycles: two per instruction, three for long branches
+/// and skips, one per interrupt or DMA transfer.
+#[derive(Clone, Debug, Default)]
+pub struct Cdp1802 {
+    registers: [u16; 16],
+    // Which registers are the program counter and the data pointer.
+    p: u8,
+    x: u8,
+    d: u8,
+    df: bool,
+    t: u8,
+    ie: bool,
+    q: bool,
+    idle: bool,
+}
+
+impl Cdp1802 {
+    /// A CPU as a reset leaves it: R0 the program counter at 0000 and
+    /// interrupts enabled.
+    pub fn new() -> Cdp1802 {
+        Cdp1802 {
+            ie: true,
+            ..Cdp1802::default()
+        }
+    }
+
+    pub fn register(&self, n: usize) -> u16 {
+        self.registers[n]
+    }
+
+    /// The Q output, which drives the VIP's speaker.
+    pub fn q(&self) -> bool {
+        self.q
+    }
+
+    /// Takes an interrupt if they are enabled: X and P are saved in T, and
+    /// R1 becomes the program counter with R2 as the data pointer. Returns
+    /// the cycles taken.
+    pub fn interrupt(&mut self) -> u32 {
+        if !self.ie {
+            return 0;
+        }
+
+        self.t = self.x << 4 | self.p;
+        self.p = 1;
+        self.x = 2;
+        self.ie = false;
+        self.idle = false;
+
+        1
+    }
+
+    /// Reads the byte R0 points at for a DMA output transfer, moving R0 on.
+    /// Takes one cycle.
+    pub fn dma_out(&mut self, bus: &mut impl Bus) -> u8 {
+        let value = bus.read(self.registers[0]);
+        self.registers[0] = self.registers[0].wrapping_add(1);
+        self.idle = false;
+
+        value
+    }
+
+    /// Executes one instruction, or waits two cycles after IDL until an
+    /// interrupt or DMA transfer. Returns the cycles taken and whether an
+    /// instruction was executed.
+    pub fn step(&mut self, bus: &mut impl Bus) -> (u32, bool) {
+        if self.idle {
+            return (2, false);
+        }
+
+        let opcode = self.fetch(bus);
+        let (i, n) = (opcode >> 4, (opcode & 0xF) as usize);
+        let x = self.x as usize;
+
+        match (i, n) {
+            (0x0, 0) => self.idle = true,
+            (0x0, _) => self.d = bus.read(self.registers[n]),
+            (0x1, _) => self.registers[n] = self.registers[n].wrapping_add(1),
+            (0x2, _) => self.registers[n] = self.registers[n].wrapping_sub(1),
+            (0x3, _) => {
+                let taken = self.condition(bus, n);
+                self.short_branch(bus, taken);
+            }
+            (0x4, _) => {
+                self.d = bus.read(self.registers[n]);
+                self.registers[n] = self.registers[n].wrapping_add(1);
+            }
+            (0x5, _) => bus.write(self.registers[n], self.d),
+            (0x6, 0) => self.registers[x] = self.registers[x].wrapping_add(1),
+            (0x6, 1..=7) => {
+                let value = bus.read(self.registers[x]);
+                bus.output(n as u8, value);
+                self.registers[x] = self.registers[x].wrapping_add(1);
+            }
+            // 68 is only an instruction on later members of the family.
+            (0x6, 8) => {}
+            (0x6, _) => {
+                self.d = bus.input(n as u8 - 8);
+                bus.write(self.registers[x], self.d);
+            }
+            (0x7, 0x0 | 0x1) => {
+                let value = bus.read(self.registers[x]);
+                self.registers[x] = self.registers[x].wrapping_add(1);
+                self.x = value >> 4;
+                self.p = value & 0xF;
+                self.ie = n == 0;
+            }
+            (0x7, 0x2) => {
+                self.d = bus.read(self.registers[x]);
+                self.registers[x] = self.registers[x].wrapping_add(1);
+            }
+            (0x7, 0x3) => {
+                bus.write(self.registers[x], self.d);
+                self.registers[x] = self.registers[x].wrapping_sub(1);
+            }
+            (0x7, 0x4) => {
+                let value = bus.read(self.registers[x]);
+                self.add(value, self.df);
+            }
+            (0x7, 0x5) => {
+                let value = bus.read(self.registers[x]);
+                self.subtract(value, self.d, self.df);
+            }
+            (0x7, 0x6) => {
+                let carry = self.df;
+                self.df = self.d & 1 != 0;
+                self.d = self.d >> 1 | (carry as u8) << 7;
+            }
+            (0x7, 0x7) => {
+                let value = bus.read(self.registers[x]);
+                self.subtract(self.d, value, self.df);
+            }
+            (0x7, 0x8) => bus.write(self.registers[x], self.t),
+            (0x7, 0x9) => {
+                self.t = self.x << 4 | self.p;
+                bus.write(self.registers[2], self.t);
+                self.x = self.p;
+                self.registers[2] = self.registers[2].wrapping_sub(1);
+            }
+            (0x7, 0xA) => self.q = false,
+            (0x7, 0xB) => self.q = true,
+            (0x7, 0xC) => {
+                let value = self.fetch(bus);
+                self.add(value, self.df);
+            }
+            (0x7, 0xD) => {
+                let value = self.fetch(bus);
+                self.subtract(value, self.d, self.df);
+            }
+            (0x7, 0xE) => {
+                let carry = self.df;
+                self.df = self.d & 0x80 != 0;
+                self.d = self.d << 1 | carry as u8;
+            }
+            (0x7, _) => {
+                let value = self.fetch(bus);
+                self.subtract(self.d, value, self.df);
+            }
+            (0x8, _) => self.d = self.registers[n] as u8,
+            (0x9, _) => self.d = (self.registers[n] >> 8) as u8,
+            (0xA, _) => self.registers[n] = self.registers[n] & 0xFF00 | self.d as u16,
+            (0xB, _) => self.registers[n] = self.registers[n] & 0x00FF | (self.d as u16) << 8,
+            (0xC, _) => {
+                self.long_branch(bus, n);
+                return (3, true);
+            }
+            (0xD, _) => self.p = n as u8,
+            (0xE, _) => self.x = n as u8,
+            (0xF, 0x6) => {
+                self.df = self.d & 1 != 0;
+                self.d >>= 1;
+            }
+            (0xF, 0xE) => {
+                self.df = self.d & 0x80 != 0;
+                self.d <<= 1;
+            }
+            (0xF, _) => {
+                // F0 to F7 take their operand from M(R(X)), F8 to FF from the
+                // byte after the instruction.
+                let value = if n < 8 {
+                    bus.read(self.registers[x])
+                } else {
+                    self.fetch(bus)
+                };
+                match n & 7 {
+                    0 => self.d = value,
+                    1 => self.d |= value,
+                    2 => self.d &= value,
+                    3 => self.d ^= value,
+                    4 => self.add(value, false),
+                    5 => self.subtract(value, self.d, true),
+                    _ => self.subtract(self.d, value, true),
+                }
+            }
+            _ => unreachable!(),
+        }
+
+        (2, true)
+    }
+
+    fn fetch(&mut self, bus: &mut impl Bus) -> u8 {
+        let p = self.p as usize;
+        let value = bus.read(self.registers[p]);
+        self.registers[p] = self.registers[p].wrapping_add(1);
+
+        value
+    }
+
+    // The condition of branch `n`, 8 to F testing the opposite of 0 to 7.
+    fn condition(&self, bus: &impl Bus, n: usize) -> bool {
+        let condition = match n & 7 {
+            0 => true,
+            1 => self.q,
+            2 => self.d == 0,
+            3 => self.df,
+            line => bus.flag(line as u8 - 3),
+        };
+
+        condition != (n >= 8)
+    }
+
+    fn short_branch(&mut self, bus: &mut impl Bus, taken: bool) {
+        let p = self.p as usize;
+        if taken {
+            let low = bus.read(self.registers[p]);
+            self.registers[p] = self.registers[p] & 0xFF00 | low as u16;
+        } else {
+            self.registers[p] = self.registers[p].wrapping_add(1);
+        }
+    }
+
+    // Long branches jump to the next two bytes, long skips pass over them.
+    fn long_branch(&mut self, bus: &mut impl Bus, n: usize) {
+        let (taken, skip) = match n {
+            0x0 => (true, false),
+            0x1 => (self.q, false),
+            0x2 => (self.d == 0, false),
+            0x3 => (self.df, false),
+            0x4 => (false, true),
+            0x5 => (!self.q, true),
+            0x6 => (self.d != 0, true),
+            0x7 => (!self.df, true),
+            0x8 => (true, true),
+            0x9 => (!self.q, false),
+            0xA => (self.d != 0, false),
+            0xB => (!self.df, false),
+            0xC => (self.ie, true),
+            0xD => (self.q, true),
+            0xE => (self.d == 0, true),
+            _ => (self.df, true),
+        };
+
+        let p = self.p as usize;
+        if taken && !skip {
+            let high = bus.read(self.registers[p]);
+            let low = bus.read(self.registers[p].wrapping_add(1));
+            self.registers[p] = u16::from_be_bytes([high, low]);
+        } else if taken || !skip {
+            // A skip taken or a branch not taken passes the address. NOP
+            // (C4) is a skip never taken.
+            self.registers[p] = self.registers[p].wrapping_add(2);
+        }
+    }
+
+    fn add(&mut self, value: u8, carry: bool) {
+        let sum = self.d as u16 + value as u16 + carry as u16;
+        self.d = sum as u8;
+        self.df = sum > 0xFF;
+    }
+
+    // D = minuend - subtrahend, with DF set when nothing was borrowed. With
+    // `no_borrow` false, a borrow from before is taken as well.
+    fn subtract(&mut self, minuend: u8, subtrahend: u8, no_borrow: bool) {
+        let difference = minuend as i16 - subtrahend as i16 - !no_borrow as i16;
+        self.d = difference as u8;
+        self.df = difference >= 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 256 bytes of RAM, flag lines set by the test and nothing on the ports.
+    struct TestBus {
+        memory: Vec<u8>,
+        flags: [bool; 4],
+    }
+
+    impl Bus for TestBus {
+        fn read(&mut self, address: u16) -> u8 {
+            self.memory[address as usize % 256]
+        }
+
+        fn write(&mut self, address: u16, value: u8) {
+            self.memory[address as usize % 256] = value;
+        }
+
+        fn output(&mut self, _port: u8, _value: u8) {}
+
+        fn input(&mut self, _port: u8) -> u8 {
+            0
+        }
+
+        fn flag(&self, line: u8) -> bool {
+            self.flags[line as usize - 1]
+        }
+    }
+
+    // A CPU about to run `program` from 0000, with R(X) = R2 pointing at
+    // 0080, which holds `operand`.
+    fn setup(program: &[u8], operand: u8) -> (Cdp1802, TestBus) {
+        let mut memory = vec![0; 256];
+        memory[..program.len()].copy_from_slice(program);
+        memory[0x80] = operand;
+
+        let mut cpu = Cdp1802::new();
+        cpu.x = 2;
+        cpu.registers[2] = 0x80;
+
+        (
+            cpu,
+            TestBus {
+                memory,
+                flags: [false; 4],
+            },
+        )
+    }
+
+    // Runs a one-byte ALU instruction with D and DF set beforehand.
+    fn alu(opcode: u8, d: u8, df: bool, operand: u8) -> (u8, bool) {
+        let (mut cpu, mut bus) = setup(&[opcode], operand);
+        cpu.d = d;
+        cpu.df = df;
+        cpu.step(&mut bus);
+
+        (cpu.d, cpu.df)
+    }
+
+    #[test]
+    fn subtractions_set_df_when_nothing_is_borrowed() {
+        // SD: M(R(X)) - D
+        assert_eq!(alu(0xF5, 3, false, 5), (2, true));
+        assert_eq!(alu(0xF5, 5, true, 3), (0xFE, false));
+        // SM: D - M(R(X))
+        assert_eq!(alu(0xF7, 5, false, 3), (2, true));
+        assert_eq!(alu(0xF7, 3, true, 5), (0xFE, false));
+        // SDB and SMB take one more when DF shows a borrow.
+        assert_eq!(alu(0x75, 3, false, 5), (1, true));
+        assert_eq!(alu(0x75, 3, true, 5), (2, true));
+        assert_eq!(alu(0x75, 5, false, 5), (0xFF, false));
+        assert_eq!(alu(0x77, 5, false, 5), (0xFF, false));
+        assert_eq!(alu(0x77, 5, true, 5), (0, true));
+        assert_eq!(alu(0x77, 6, false, 5), (0, true));
+    }
+
+    #[test]
+    fn additions_carry_into_df() {
+        assert_eq!(alu(0xF4, 0xFF, true, 1), (0, true));
+        assert_eq!(alu(0x74, 0xFF, true, 0), (0, true));
+        assert_eq!(alu(0x74, 1, true, 1), (3, false));
+    }
+
+    #[test]
+    fn shifts_through_df() {
+        // SHRC and SHLC rotate through DF, SHR and SHL shift in a zero.
+        assert_eq!(alu(0x76, 0x81, false, 0), (0x40, true));
+        assert_eq!(alu(0x76, 0x40, true, 0), (0xA0, false));
+        assert_eq!(alu(0x7E, 0x81, true, 0), (0x03, true));
+        assert_eq!(alu(0x7E, 0x40, false, 0), (0x80, false));
+        assert_eq!(alu(0xF6, 0x81, false, 0), (0x40, true));
+        assert_eq!(alu(0xFE, 0x81, true, 0), (0x02, true));
+    }
+
+    #[test]
+    fn long_branches_and_skips() {
+        // LBR jumps, in three cycles.
+        let (mut cpu, mut bus) = setup(&[0xC0, 0x12, 0x34], 0);
+        assert_eq!(cpu.step(&mut bus), (3, true));
+        assert_eq!(cpu.register(0), 0x1234);
+
+        // LBZ not taken passes the address.
+        let (mut cpu, mut bus) = setup(&[0xC2, 0x12, 0x34], 0);
+        cpu.d = 1;
+        cpu.step(&mut bus);
+        assert_eq!(cpu.register(0), 3);
+
+        // LSZ taken skips two bytes, not taken goes on after itself.
+        let (mut cpu, mut bus) = setup(&[0xCE], 0);
+        cpu.step(&mut bus);
+        assert_eq!(cpu.register(0), 3);
+        let (mut cpu, mut bus) = setup(&[0xCE], 0);
+        cpu.d = 1;
+        cpu.step(&mut bus);
+        assert_eq!(cpu.register(0), 1);
+
+        // NOP is a skip never taken, LSIE skips while interrupts are enabled.
+        let (mut cpu, mut bus) = setup(&[0xC4], 0);
+        assert_eq!(cpu.step(&mut bus), (3, true));
+        assert_eq!(cpu.register(0), 1);
+        let (mut cpu, mut bus) = setup(&[0xCC], 0);
+        cpu.step(&mut bus);
+        assert_eq!(cpu.register(0), 3);
+
+        // LBQ tests Q, LBNF tests DF.
+        let (mut cpu, mut bus) = setup(&[0x7B, 0xC1, 0x00, 0x40], 0);
+        cpu.step(&mut bus);
+        cpu.step(&mut bus);
+        assert_eq!(cpu.register(0), 0x0040);
+        let (mut cpu, mut bus) = setup(&[0xCB, 0x00, 0x40], 0);
+        cpu.df = true;
+        cpu.step(&mut bus);
+        assert_eq!(cpu.register(0), 3);
+    }
+
+    #[test]
+    fn short_branches_test_the_flag_lines() {
+        // B3 taken stays in the page, BN3 falls through.
+        let (mut cpu, mut bus) = setup(&[0x36, 0x40], 0);
+        bus.flags[2] = true;
+        cpu.step(&mut bus);
+        assert_eq!(cpu.register(0), 0x40);
+        let (mut cpu, mut bus) = setup(&[0x3E, 0x40], 0);
+        bus.flags[2] = true;
+        cpu.step(&mut bus);
+        assert_eq!(cpu.register(0), 2);
+    }
+
+    #[test]
+    fn sep_and_sex_select_registers() {
+        // SEP 3 makes R3 the program counter, SEX 5 R5 the data pointer.
+        let (mut cpu, mut bus) = setup(&[0xD3], 0);
+        bus.memory[0x10] = 0xE5;
+        cpu.registers[3] = 0x10;
+        cpu.step(&mut bus);
+        assert_eq!(cpu.p, 3);
+        cpu.step(&mut bus);
+        assert_eq!((cpu.x, cpu.register(3)), (5, 0x11));
+    }
+
+    #[test]
+    fn mark_and_return() {
+        // MARK saves X and P at R2 and moves R2 down, with X = P.
+        let (mut cpu, mut bus) = setup(&[0x79], 0);
+        cpu.x = 4;
+        cpu.step(&mut bus);
+        assert_eq!(bus.memory[0x80], 0x40);
+        assert_eq!((cpu.t, cpu.x, cpu.register(2)), (0x40, 0, 0x7F));
+
+        // RET restores X and P from M(R(X)) and enables interrupts, DIS
+        // disables them.
+        let (mut cpu, mut bus) = setup(&[0x70], 0x23);
+        cpu.ie = false;
+        cpu.step(&mut bus);
+        assert_eq!((cpu.x, cpu.p, cpu.ie), (2, 3, true));
+        assert_eq!(cpu.register(2), 0x81);
+        let (mut cpu, mut bus) = setup(&[0x71], 0x23);
+        cpu.step(&mut bus);
+        assert!(!cpu.ie);
+    }
+
+    #[test]
+    fn interrupts() {
+        // T keeps X and P, R1 runs the routine with R2 as the data pointer.
+        let (mut cpu, _) = setup(&[0x00], 0);
+        cpu.p = 3;
+        cpu.x = 5;
+        assert_eq!(cpu.interrupt(), 1);
+        assert_eq!((cpu.t, cpu.p, cpu.x, cpu.ie), (0x53, 1, 2, false));
+
+        // No interrupt while they are disabled.
+        assert_eq!(cpu.interrupt(), 0);
+        assert_eq!(cpu.p, 1);
+
+        // An interrupt ends IDL.
+        let (mut cpu, mut bus) = setup(&[0x00], 0);
+        cpu.step(&mut bus);
+        assert_eq!(cpu.step(&mut bus), (2, false));
+        cpu.interrupt();
+        cpu.registers[1] = 0x10;
+        bus.memory[0x10] = 0x7B;
+        assert_eq!(cpu.step(&mut bus), (2, true));
+        assert!(cpu.q());
+    }
+}
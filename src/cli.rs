@@ -0,0 +1,431 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+
+use crate::{
+    audio::{SoundSink, MAX_TONE, MIN_TONE},
+    config::Settings,
+    filter::FilterMode,
+    font::FontSet,
+    glyphs::RenderMode,
+    graphics::MAX_PIXEL_SIZE,
+    keymap::KeyLayout,
+    machine::Core,
+    memory::{parse_address, parse_memory_size, Region},
+    palette::Palette,
+    policy::OpcodePolicy,
+    quirks::Platform,
+    recording::RecordFormat,
+    screenshot::ImageFormat,
+    stack::MAX_STACK_DEPTH,
+    timing::Timing,
+};
+
+#[derive(Parser)]
+#[command(
+    name = "emulator",
+    version,
+    about = "A CHIP-8 emulator for the terminal"
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+
+    /// Configuration file to read instead of the user's default one
+    #[arg(long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Ignore the configuration file
+    #[arg(long, global = true, conflicts_with = "config")]
+    pub no_config: bool,
+
+    /// ROM database (a programs.json file) to use next to the bundled one
+    #[arg(long, global = true, value_name = "FILE")]
+    pub database: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run a ROM in the terminal
+    Run(RunArgs),
+    /// Print the instructions in a ROM
+    Disasm(DisasmArgs),
+    /// Assemble a source file into a ROM
+    Asm(AsmArgs),
+    /// Show information about a ROM
+    Info(InfoArgs),
+    /// Measure how fast a ROM is emulated without rendering
+    Bench(BenchArgs),
+    /// Run a ROM headless and print or check the final screen
+    Test(TestArgs),
+}
+
+// The setting of a flag that has a --no- form to turn off what the
+// configuration turned on. Clap keeps only the last of the two.
+fn switch(on: bool, off: bool) -> Option<bool> {
+    if on {
+        Some(true)
+    } else if off {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Options that shape the emulated machine, shared by every command that
+/// executes a ROM. Options left out are taken from the configuration file.
+#[derive(Args)]
+pub struct MachineArgs {
+    /// Instructions executed per 60 Hz frame [default: 10]
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..=100_000))]
+    pub ipf: Option<u32>,
+
+    /// How many instructions run in each frame: the IPF, or as many as fit
+    /// in the time the COSMAC VIP took for them [default: ipf]
+    #[arg(long, value_enum)]
+    pub timing: Option<Timing>,
+
+    /// Platform the ROM was written for [default: chip8]
+    #[arg(long, value_enum)]
+    pub platform: Option<Platform>,
+
+    /// Quirk preset, defaults to the one of the platform
+    #[arg(long, value_enum, value_name = "PRESET")]
+    pub quirks: Option<Platform>,
+
+    /// Seed for the random number generator used by CXKK
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// What to do with opcodes that cannot be decoded or executed [default:
+    /// halt]
+    #[arg(long, value_enum, value_name = "POLICY")]
+    pub on_undefined: Option<OpcodePolicy>,
+
+    /// Return addresses the stack has room for [default: 12 for chip8
+    /// quirks, 16 otherwise]
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..=MAX_STACK_DEPTH as i64))]
+    pub stack_depth: Option<u16>,
+
+    /// Keep the stack in memory below 0xED0, like the VIP interpreter
+    #[arg(long, overrides_with = "no_memory_stack")]
+    pub memory_stack: bool,
+
+    /// Keep the stack outside of memory, overriding the configuration
+    #[arg(long, overrides_with = "memory_stack")]
+    pub no_memory_stack: bool,
+
+    /// Keep the display in the last 256 bytes of memory, 0xF00-0xFFF with
+    /// 4K, where programs can read and write it like on the VIP
+    #[arg(long, overrides_with = "no_memory_display")]
+    pub memory_display: bool,
+
+    /// Keep the display outside of memory, overriding the configuration
+    #[arg(long, overrides_with = "memory_display")]
+    pub no_memory_display: bool,
+
+    /// Memory size, from 1K to 64K [default: 4K]
+    #[arg(long, value_name = "SIZE", value_parser = parse_memory_size)]
+    pub memory_size: Option<usize>,
+
+    /// Address programs are loaded and started at, e.g. 0x600 for the
+    /// ETI-660 [default: 0x200]
+    #[arg(long, value_name = "ADDRESS", value_parser = parse_address)]
+    pub load_address: Option<u16>,
+
+    /// Address of the hexadecimal font [default: 0x50]
+    #[arg(long, value_name = "ADDRESS", value_parser = parse_address)]
+    pub font_address: Option<u16>,
+
+    /// Address ranges kept for the interpreter that programs may not be
+    /// loaded over, e.g. 0xEA0-0xFFF
+    #[arg(long, value_name = "RANGES", value_delimiter = ',')]
+    pub reserved: Option<Vec<Region>>,
+
+    /// Built-in font set [default: chip48]
+    #[arg(long, value_enum, value_name = "SET")]
+    pub font: Option<FontSet>,
+
+    /// Font to load instead of a built-in one: 80 bytes of 4x5 glyphs,
+    /// optionally followed by 10 bytes for each 8x10 glyph
+    #[arg(long, value_name = "FILE")]
+    pub font_file: Option<PathBuf>,
+
+    /// Emulator that runs the program [default: hle]
+    #[arg(long, value_enum)]
+    pub core: Option<Core>,
+
+    /// The original CHIP-8 interpreter for the VIP core, loaded at 0x000
+    #[arg(long, value_name = "FILE")]
+    pub interpreter: Option<PathBuf>,
+
+    /// The VIP operating system ROM, which starts the interpreter and holds
+    /// the font it uses
+    #[arg(long, value_name = "FILE")]
+    pub vip_rom: Option<PathBuf>,
+}
+
+impl MachineArgs {
+    pub fn settings(&self) -> Settings {
+        Settings {
+            ipf: self.ipf,
+            timing: self.timing,
+            platform: self.platform,
+            quirks: self.quirks,
+            seed: self.seed,
+            on_undefined: self.on_undefined,
+            stack_depth: self.stack_depth,
+            memory_stack: switch(self.memory_stack, self.no_memory_stack),
+            memory_display: switch(self.memory_display, self.no_memory_display),
+            memory_size: self.memory_size,
+            load_address: self.load_address,
+            font_address: self.font_address,
+            reserved: self.reserved.clone(),
+            font: self.font,
+            font_file: self.font_file.clone(),
+            core: self.core,
+            interpreter: self.interpreter.clone(),
+            vip_rom: self.vip_rom.clone(),
+            ..Settings::default()
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct RunArgs {
+    /// ROM file to run
+    pub rom: PathBuf,
+
+    #[command(flatten)]
+    pub machine: MachineArgs,
+
+    /// Colour palette: a preset such as green or octo, or #rrggbb colours
+    /// separated by commas, background first [default: mono]
+    #[arg(long)]
+    pub palette: Option<Palette>,
+
+    /// Keyboard layout used to map keys onto the hex keypad [default: qwerty]
+    #[arg(long, value_enum, value_name = "LAYOUT")]
+    pub keys: Option<KeyLayout>,
+
+    /// How pixels are drawn with characters [default: auto]
+    #[arg(long, value_enum, value_name = "MODE")]
+    pub renderer: Option<RenderMode>,
+
+    /// Enlarge the screen by the largest whole factor that fits the terminal
+    #[arg(long, overrides_with = "no_scale")]
+    pub scale: bool,
+
+    /// Do not enlarge the screen, overriding the configuration
+    #[arg(long, overrides_with = "scale")]
+    pub no_scale: bool,
+
+    /// Screen pixels per CHIP-8 pixel for the kitty and sixel renderers and
+    /// for screenshots [default: 8]
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..=MAX_PIXEL_SIZE as i64))]
+    pub pixel_size: Option<u16>,
+
+    /// Filter against flicker between the emulated display and the screen
+    /// [default: none]
+    #[arg(long, value_enum)]
+    pub filter: Option<FilterMode>,
+
+    /// Image format of screenshots [default: png]
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    pub screenshot_format: Option<ImageFormat>,
+
+    /// Directory screenshots are saved in [default: the current directory]
+    #[arg(long, value_name = "DIR")]
+    pub screenshot_dir: Option<PathBuf>,
+
+    /// Save a screenshot after each of these frames, e.g. 60,600
+    #[arg(long, value_name = "FRAMES", value_delimiter = ',')]
+    pub screenshot_at: Vec<u64>,
+
+    /// Record the run to FILE: an asciinema recording when it ends in .cast,
+    /// an animated GIF otherwise
+    #[arg(long, value_name = "FILE")]
+    pub record: Option<PathBuf>,
+
+    /// Format of recordings started with F9 [default: gif]
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    pub record_format: Option<RecordFormat>,
+
+    /// Save the keypad events of the run to FILE, as a movie --replay plays
+    #[arg(long, value_name = "FILE")]
+    pub record_input: Option<PathBuf>,
+
+    /// Press and release keys as the movie in FILE does, in a headless run
+    #[arg(long, value_name = "FILE")]
+    pub replay: Option<PathBuf>,
+
+    /// Where the buzzer is played [default: bell, or off when headless]
+    #[arg(long, value_enum, value_name = "SINK")]
+    pub sound: Option<SoundSink>,
+
+    /// File the wav and pcm sinks write to, pcm writes to standard output
+    /// without one
+    #[arg(long, value_name = "FILE")]
+    pub sound_file: Option<PathBuf>,
+
+    /// Pitch of the buzzer in Hz [default: 440]
+    #[arg(long, value_name = "HZ", value_parser = clap::value_parser!(u16).range(MIN_TONE as i64..=MAX_TONE as i64))]
+    pub tone: Option<u16>,
+
+    /// Volume of the buzzer in percent [default: 25]
+    #[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub volume: Option<u8>,
+
+    /// Start with emulation paused, press space to resume
+    #[arg(long, overrides_with = "no_paused")]
+    pub paused: bool,
+
+    /// Start running, overriding the configuration
+    #[arg(long, overrides_with = "paused")]
+    pub no_paused: bool,
+
+    /// Run without a terminal display or keyboard input
+    #[arg(long, overrides_with = "no_headless")]
+    pub headless: bool,
+
+    /// Run in the terminal, overriding the configuration
+    #[arg(long, overrides_with = "headless")]
+    pub no_headless: bool,
+
+    /// Stop after this many frames
+    #[arg(long, value_name = "N")]
+    pub frames: Option<u64>,
+}
+
+impl RunArgs {
+    pub fn settings(&self) -> Settings {
+        Settings {
+            palette: self.palette,
+            keys: self.keys,
+            renderer: self.renderer,
+            scale: switch(self.scale, self.no_scale),
+            pixel_size: self.pixel_size,
+            filter: self.filter,
+            screenshot_format: self.screenshot_format,
+            screenshot_dir: self.screenshot_dir.clone(),
+            record_format: self.record_format,
+            sound: self.sound,
+            sound_file: self.sound_file.clone(),
+            tone: self.tone,
+            volume: self.volume,
+            paused: switch(self.paused, self.no_paused),
+            headless: switch(self.headless, self.no_headless),
+            frames: self.frames,
+            ..self.machine.settings()
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct DisasmArgs {
+    /// ROM file to disassemble
+    pub rom: PathBuf,
+
+    /// Address the ROM is loaded at
+    #[arg(long, value_name = "ADDRESS", value_parser = parse_address, default_value = "0x200")]
+    pub load_address: u16,
+}
+
+#[derive(Args)]
+pub struct AsmArgs {
+    /// Assembly source file
+    pub source: PathBuf,
+
+    /// Output file, defaults to the source with a .ch8 extension
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Address the program will be loaded at
+    #[arg(long, value_name = "ADDRESS", value_parser = parse_address, default_value = "0x200")]
+    pub load_address: u16,
+}
+
+#[derive(Args)]
+pub struct InfoArgs {
+    /// ROM file to inspect
+    pub rom: PathBuf,
+}
+
+#[derive(Args)]
+pub struct BenchArgs {
+    /// ROM file to benchmark
+    pub rom: PathBuf,
+
+    #[command(flatten)]
+    pub machine: MachineArgs,
+
+    /// Number of frames to emulate
+    #[arg(long, value_name = "N", default_value_t = 6000)]
+    pub frames: u64,
+}
+
+#[derive(Args)]
+pub struct TestArgs {
+    /// ROM file to test
+    pub rom: PathBuf,
+
+    #[command(flatten)]
+    pub machine: MachineArgs,
+
+    /// Number of frames to emulate before taking the snapshot
+    #[arg(long, value_name = "N", default_value_t = 600)]
+    pub frames: u64,
+
+    /// Text snapshot the final screen must match
+    #[arg(long, value_name = "FILE")]
+    pub expect: Option<PathBuf>,
+
+    /// Run the ROM on both the built-in interpreter and the VIP core and
+    /// check that their final screens match
+    #[arg(long, conflicts_with = "expect")]
+    pub compare: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_settings(args: &[&str]) -> Settings {
+        let cli = Cli::try_parse_from(["emulator", "run", "rom.ch8"].iter().chain(args)).unwrap();
+        let Command::Run(run) = cli.command else {
+            unreachable!()
+        };
+        run.settings()
+    }
+
+    #[test]
+    fn negated_flags_override_the_configuration() {
+        let config = Settings {
+            memory_stack: Some(true),
+            memory_display: Some(true),
+            scale: Some(true),
+            paused: Some(true),
+            headless: Some(true),
+            ..Settings::default()
+        };
+
+        let settings = config.clone().overridden_by(run_settings(&[
+            "--no-memory-stack",
+            "--no-memory-display",
+            "--no-scale",
+            "--no-paused",
+            "--no-headless",
+        ]));
+        assert_eq!(settings.memory_stack, Some(false));
+        assert_eq!(settings.memory_display, Some(false));
+        assert_eq!(settings.scale, Some(false));
+        assert_eq!(settings.paused, Some(false));
+        assert_eq!(settings.headless, Some(false));
+
+        let settings = config.clone().overridden_by(run_settings(&[]));
+        assert_eq!(settings.scale, Some(true));
+
+        let settings = config.overridden_by(run_settings(&["--no-scale", "--scale"]));
+        assert_eq!(settings.scale, Some(true));
+    }
+}
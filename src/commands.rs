@@ -0,0 +1,613 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::Instant;
+
+use clap::ValueEnum;
+
+use crate::{
+    analysis::{self, Family},
+    assembler,
+    audio::{Audio, SoundSink, DEFAULT_TONE, DEFAULT_VOLUME},
+    cli::{AsmArgs, BenchArgs, Cli, DisasmArgs, InfoArgs, RunArgs, TestArgs},
+    config::{Config, Settings},
+    database::Database,
+    disassembler::disassemble,
+    display::Display,
+    font::Font,
+    frontend::{self, FRAME_DURATION},
+    graphics::DEFAULT_PIXEL_SIZE,
+    machine::{Core, Machine},
+    memory::Memory,
+    movie::Movie,
+    palette::format_palette,
+    policy::OpcodePolicy,
+    processor::{Chip8, Fault, DEFAULT_INSTRUCTIONS_PER_FRAME},
+    quirks::Quirks,
+    recording::{GifRecorder, RecordFormat},
+    rom::{crc32, Rom},
+    screenshot,
+    stack::Stack,
+    vip::Vip,
+};
+
+/// Everything besides the command-line options that decides how a ROM runs.
+pub struct Context {
+    config: Config,
+    database: Database,
+}
+
+/// Reads the configuration file named on the command line, or the user's
+/// default one when it exists, and the ROM database. Unknown configuration
+/// keys are reported on stderr.
+pub fn load_context(cli: &Cli) -> Result<Context, Box<dyn Error>> {
+    let config = match (&cli.config, Config::default_path()) {
+        _ if cli.no_config => Config::default(),
+        (Some(path), _) => Config::load(path)?,
+        (None, Some(path)) if path.exists() => Config::load(&path)?,
+        (None, _) => Config::default(),
+    };
+
+    for warning in &config.warnings {
+        eprintln!("warning: {}", warning);
+    }
+
+    let user_database = Config::directory()
+        .map(|directory| directory.join("programs.json"))
+        .filter(|path| path.exists());
+    let database_path = cli
+        .database
+        .clone()
+        .or_else(|| config.database.clone())
+        .or(user_database);
+    let database = Database::load(database_path.as_deref())?;
+
+    Ok(Context { config, database })
+}
+
+/// The ROM database's recommendations come first, then the configuration
+/// file and finally the command line. The font recommended for the built-in
+/// interpreter is dropped for the VIP core, which has the one in its ROM.
+fn resolve_settings(context: &Context, rom: &Rom, command_line: Settings) -> Settings {
+    let recommended = context
+        .database
+        .lookup(&rom.sha1)
+        .map(|entry| entry.settings())
+        .unwrap_or_default();
+    let chosen = context
+        .config
+        .settings_for(&rom.file_name(), &rom.sha1)
+        .overridden_by(command_line);
+
+    if chosen.core == Some(Core::Vip) {
+        return Settings {
+            font: chosen.font,
+            ..recommended.overridden_by(chosen)
+        };
+    }
+    recommended.overridden_by(chosen)
+}
+
+/// The font file if one is given, else the built-in font set.
+fn load_font(settings: &Settings) -> Result<Font, String> {
+    match &settings.font_file {
+        Some(path) => Font::read(path),
+        None => Ok(Font::builtin(settings.font.unwrap_or_default())),
+    }
+}
+
+fn load_machine(rom: &Rom, settings: &Settings) -> Result<Box<dyn Machine>, Box<dyn Error>> {
+    if settings.core == Some(Core::Vip) {
+        return Ok(Box::new(load_vip(rom, settings)?));
+    }
+
+    let platform = settings.platform.unwrap_or_default();
+    let interpreter = settings.quirks.unwrap_or(platform);
+    let stack_depth = settings
+        .stack_depth
+        .unwrap_or_else(|| interpreter.stack_depth());
+    let memory_stack = settings.memory_stack.unwrap_or(false);
+
+    let mut map = settings.memory_map();
+    if memory_stack {
+        map.reserved
+            .push(Stack::memory_region(stack_depth, map.size)?);
+    }
+    let memory = Memory::with_program(map, load_font(settings)?, &rom.data)
+        .map_err(|e| format!("ROM cannot be loaded: {}", e))?;
+
+    let quirks = settings
+        .quirk_overrides
+        .apply(Quirks::for_platform(interpreter));
+    let mut chip8 = Chip8::new(memory, quirks);
+    chip8.set_instructions_per_frame(settings.ipf.unwrap_or(DEFAULT_INSTRUCTIONS_PER_FRAME));
+    chip8.set_timing(settings.timing.unwrap_or_default());
+    chip8.set_stack(stack_depth, memory_stack);
+
+    if let Some(seed) = settings.seed {
+        chip8.seed(seed);
+    }
+
+    Ok(Box::new(chip8))
+}
+
+/// A COSMAC VIP running the interpreter from the settings. Quirks, speed
+/// and the other options of the built-in interpreter do not apply.
+fn load_vip(rom: &Rom, settings: &Settings) -> Result<Vip, Box<dyn Error>> {
+    let read = |path: &Path| {
+        fs::read(path).map_err(|e| format!("could not read '{}': {}", path.display(), e))
+    };
+    let interpreter = settings
+        .interpreter
+        .as_deref()
+        .ok_or("the VIP core needs the original interpreter, give it with --interpreter")?;
+    // The interpreter and the ROM decide where the font and display are.
+    let ignored = [
+        ("--font-address", settings.font_address.is_some()),
+        ("--reserved", settings.reserved.is_some()),
+        ("--memory-display", settings.memory_display == Some(true)),
+        ("--font", settings.font.is_some()),
+        ("--font-file", settings.font_file.is_some()),
+    ];
+    if let Some((option, _)) = ignored.iter().find(|(_, set)| *set) {
+        return Err(format!("{} does not apply to the VIP core", option).into());
+    }
+    // The interpreter's display interrupt routine and font are in the ROM.
+    let vip_rom = settings
+        .vip_rom
+        .as_deref()
+        .ok_or("the VIP core needs the VIP operating system ROM, give it with --vip-rom")?;
+
+    let vip = Vip::new(
+        &read(interpreter)?,
+        read(vip_rom)?,
+        &settings.memory_map(),
+        &rom.data,
+    )
+    .map_err(|e| format!("ROM cannot be loaded: {}", e))?;
+
+    Ok(vip)
+}
+
+/// Runs whole frames without a terminal. There is nobody to resume a
+/// `break`, so it halts just like `halt`.
+fn run_frames(
+    chip8: &mut dyn Machine,
+    frames: u64,
+    on_undefined: OpcodePolicy,
+) -> Result<(), Fault> {
+    for _ in 0..frames {
+        while !chip8.frame_finished() {
+            if let Err(fault) = chip8.emulate_cycle() {
+                match on_undefined {
+                    OpcodePolicy::Halt | OpcodePolicy::Break => return Err(fault),
+                    OpcodePolicy::Skip => {}
+                    OpcodePolicy::Log => eprintln!("warning: {}", fault),
+                }
+            }
+        }
+        chip8.end_frame();
+    }
+
+    Ok(())
+}
+
+/// Renders the display as text, one line per row with `#` for lit pixels.
+fn snapshot(display: &Display) -> String {
+    let mut text = String::new();
+
+    for y in 0..display.height() {
+        for x in 0..display.width() {
+            text.push(if display.get_pixel(x, y) == 1 {
+                '#'
+            } else {
+                '.'
+            });
+        }
+        text.push('\n');
+    }
+
+    text
+}
+
+pub fn run(args: RunArgs, context: &Context) -> Result<(), Box<dyn Error>> {
+    let rom = Rom::read(&args.rom)?;
+    let settings = resolve_settings(context, &rom, args.settings());
+    let mut chip8 = load_machine(&rom, &settings)?;
+    let on_undefined = settings.on_undefined.unwrap_or_default();
+    let palette = settings.palette.unwrap_or_default();
+    let pixel_size = settings.pixel_size.unwrap_or(DEFAULT_PIXEL_SIZE);
+    let screenshot_format = settings.screenshot_format.unwrap_or_default();
+    let screenshot_dir = settings.screenshot_dir.clone().unwrap_or_default();
+    let headless = settings.headless.unwrap_or(false);
+
+    // The terminal bell would only end up in the output of a headless run,
+    // and raw samples on standard output would garble the screen otherwise.
+    let sound = settings.sound.unwrap_or(if headless {
+        SoundSink::Off
+    } else {
+        SoundSink::Bell
+    });
+    if sound == SoundSink::Pcm && settings.sound_file.is_none() && !headless {
+        return Err(
+            "--sound pcm writes to standard output only in headless runs, \
+                    give a --sound-file, e.g. a named pipe"
+                .into(),
+        );
+    }
+    let mut audio = Audio::open(
+        sound,
+        settings.sound_file.as_deref(),
+        settings.tone.unwrap_or(DEFAULT_TONE),
+        settings.volume.unwrap_or(DEFAULT_VOLUME),
+    )?;
+
+    if headless && args.record_input.is_some() {
+        return Err("a headless run reads no keys, there is no input to record".into());
+    }
+    if !headless && args.replay.is_some() {
+        return Err("movies are replayed in headless runs, add --headless".into());
+    }
+
+    if !headless {
+        return frontend::run(
+            chip8.as_mut(),
+            audio,
+            frontend::Options {
+                rom: args.rom,
+                palette,
+                keys: settings.keys.unwrap_or_default(),
+                renderer: settings.renderer.unwrap_or_default(),
+                scale: settings.scale.unwrap_or(false),
+                pixel_size,
+                filter: settings.filter.unwrap_or_default(),
+                screenshot_format,
+                screenshot_dir,
+                screenshot_at: args.screenshot_at,
+                record: args.record,
+                record_format: settings.record_format.unwrap_or_default(),
+                record_input: args.record_input,
+                paused: settings.paused.unwrap_or(false),
+                frames: settings.frames,
+                on_undefined,
+            },
+        );
+    }
+
+    // Without a frame limit, a replay ends with the movie and a run for
+    // screenshots after the last one.
+    let movie = args.replay.as_deref().map(Movie::read).transpose()?;
+    let frames = settings
+        .frames
+        .or_else(|| movie.as_ref().map(Movie::length))
+        .or_else(|| args.screenshot_at.iter().max().copied());
+    let mut gif = match &args.record {
+        Some(path) if RecordFormat::for_path(path) == RecordFormat::Cast => {
+            return Err("asciinema recordings copy what is drawn in the terminal, \
+                        a headless run can only record a GIF"
+                .into())
+        }
+        Some(_) if frames.is_none() => {
+            return Err("a headless run needs --frames to know when the recording ends".into())
+        }
+        Some(path) => Some(GifRecorder::create(
+            path,
+            chip8.display(),
+            &palette,
+            pixel_size,
+        )?),
+        None => None,
+    };
+
+    // Raw samples on standard output leave it to the sound alone.
+    let pcm_on_stdout = sound == SoundSink::Pcm && settings.sound_file.is_none();
+    let saved = |path: &Path| {
+        if pcm_on_stdout {
+            eprintln!("Saved {}", path.display());
+        } else {
+            println!("Saved {}", path.display());
+        }
+    };
+
+    let mut frame: u64 = 0;
+    let mut next_frame = Instant::now();
+
+    while frames.is_none_or(|limit| frame < limit) {
+        if let Some(movie) = &movie {
+            movie.replay(frame, chip8.as_mut());
+        }
+        run_frames(chip8.as_mut(), 1, on_undefined)?;
+        frame += 1;
+
+        if let Some(gif) = &mut gif {
+            gif.frame(chip8.display())?;
+        }
+        if let Some(audio) = &mut audio {
+            audio.frame(chip8.buzzing(), chip8.audio_pattern())?;
+        }
+
+        if args.screenshot_at.contains(&frame) {
+            let path = screenshot::frame_path(&screenshot_dir, &args.rom, frame, screenshot_format);
+            screenshot::save(
+                chip8.display(),
+                &palette,
+                pixel_size,
+                screenshot_format,
+                &path,
+            )?;
+            saved(&path);
+        }
+
+        next_frame += FRAME_DURATION;
+        let now = Instant::now();
+        if next_frame > now {
+            thread::sleep(next_frame - now);
+        } else {
+            next_frame = now;
+        }
+    }
+
+    if let Some(audio) = audio {
+        audio.finish()?;
+    }
+    if let Some(gif) = gif {
+        let path = gif.path().to_path_buf();
+        gif.finish()?;
+        saved(&path);
+    }
+
+    Ok(())
+}
+
+pub fn disasm(args: DisasmArgs) -> Result<(), Box<dyn Error>> {
+    let rom = Rom::read(&args.rom)?;
+
+    for (index, chunk) in rom.data.chunks(2).enumerate() {
+        let address = args.load_address as usize + index * 2;
+
+        match chunk {
+            [high, low] => {
+                let word = u16::from_be_bytes([*high, *low]);
+                println!("{:04X}: {:04X}  {}", address, word, disassemble(word));
+            }
+            [byte] => println!("{:04X}: {:02X}    DB {:#04X}", address, byte, byte),
+            _ => unreachable!(),
+        }
+    }
+
+    Ok(())
+}
+
+pub fn asm(args: AsmArgs) -> Result<(), Box<dyn Error>> {
+    let source = fs::read_to_string(&args.source)
+        .map_err(|e| format!("could not read '{}': {}", args.source.display(), e))?;
+
+    let rom = assembler::assemble(&source, args.load_address)
+        .map_err(|e| format!("{}: {}", args.source.display(), e))?;
+
+    let output = args
+        .output
+        .unwrap_or_else(|| args.source.with_extension("ch8"));
+    fs::write(&output, &rom)
+        .map_err(|e| format!("could not write '{}': {}", output.display(), e))?;
+
+    println!("Wrote {} bytes to {}", rom.len(), output.display());
+
+    Ok(())
+}
+
+pub fn info(args: InfoArgs, context: &Context) -> Result<(), Box<dyn Error>> {
+    let rom = Rom::read(&args.rom)?;
+
+    println!("File:       {}", args.rom.display());
+    println!("Size:       {} bytes", rom.data.len());
+    println!("SHA-1:      {}", rom.sha1);
+    println!("CRC-32:     {:08x}", crc32(&rom.data));
+    let settings = resolve_settings(context, &rom, Settings::default());
+    let map = settings.memory_map();
+    println!(
+        "Load range: {:#05X}-{:#05X}",
+        map.program_address,
+        map.program_address as usize + rom.data.len().max(1) - 1
+    );
+
+    if let Err(e) = Memory::with_program(map.clone(), load_font(&settings)?, &rom.data) {
+        println!("Warning:    the ROM cannot be loaded, {}", e);
+    }
+
+    let analysis = analysis::analyze(&rom.data, map.program_address);
+    let reached: usize = analysis.families.values().sum();
+    let families: Vec<String> = analysis
+        .families
+        .iter()
+        .map(|(family, count)| format!("{} {}", family, count))
+        .collect();
+
+    println!(
+        "Code:       {} instructions reached, {} bytes of data",
+        reached, analysis.unreached_bytes
+    );
+    println!("Families:   {}", families.join(", "));
+    println!(
+        "Platform:   {} (guessed from the instructions used)",
+        analysis.platform().to_possible_value().unwrap().get_name()
+    );
+    if analysis.computed_jumps {
+        println!("Warning:    BNNN jumps were found, code they lead to may not have been scanned");
+    }
+
+    if !analysis.unsupported.is_empty() {
+        println!("Unsupported instructions:");
+        for (address, word) in analysis.unsupported.iter().take(16) {
+            let family = analysis::family(*word);
+            let note = match family {
+                Family::MachineCode => "machine code call",
+                Family::Invalid => "not an instruction",
+                _ => "not emulated",
+            };
+            println!("  {:04X}: {:04X}  {} ({})", address, word, family, note);
+        }
+        if analysis.unsupported.len() > 16 {
+            println!("  ... and {} more", analysis.unsupported.len() - 16);
+        }
+    }
+
+    let Some(entry) = context.database.lookup(&rom.sha1) else {
+        println!("Database:   not found, defaults will be used");
+        return Ok(());
+    };
+
+    println!("Title:      {}", entry.program.title);
+    if let Some(file) = &entry.rom.file {
+        println!("Known as:   {}", file);
+    }
+    if !entry.program.authors.is_empty() {
+        println!("Authors:    {}", entry.program.authors.join(", "));
+    }
+    if let Some(release) = &entry.program.release {
+        println!("Release:    {}", release);
+    }
+    if !entry.platform_names().is_empty() {
+        println!("Platforms:  {}", entry.platform_names().join(", "));
+    }
+
+    let settings = entry.settings();
+    if let Some(ipf) = settings.ipf {
+        println!("Speed:      {} instructions per frame", ipf);
+    }
+    if let Some(platform) = settings.platform {
+        let quirks = settings
+            .quirk_overrides
+            .apply(Quirks::for_platform(platform));
+        println!("Quirks:     {}", quirks);
+    }
+    if let Some(font) = settings.font {
+        println!(
+            "Font:       {}",
+            font.to_possible_value().unwrap().get_name()
+        );
+    }
+    if let Some(palette) = entry.palette() {
+        println!("Colours:    {}", format_palette(&palette));
+    }
+    let key_hints = entry.key_hints();
+    if !key_hints.is_empty() {
+        let hints: Vec<String> = key_hints
+            .iter()
+            .map(|(action, key)| format!("{}={:X}", action, key))
+            .collect();
+        println!("Keys:       {}", hints.join(" "));
+    }
+    if let Some(description) = &entry.program.description {
+        println!();
+        println!("{}", description);
+    }
+
+    Ok(())
+}
+
+pub fn bench(args: BenchArgs, context: &Context) -> Result<(), Box<dyn Error>> {
+    let rom = Rom::read(&args.rom)?;
+    let settings = resolve_settings(context, &rom, args.machine.settings());
+    let mut chip8 = load_machine(&rom, &settings)?;
+
+    let start = Instant::now();
+    run_frames(
+        chip8.as_mut(),
+        args.frames,
+        settings.on_undefined.unwrap_or_default(),
+    )?;
+    let elapsed = start.elapsed().as_secs_f64().max(f64::EPSILON);
+
+    let instructions = chip8.instructions();
+    println!("Frames:       {}", args.frames);
+    println!("Instructions: {}", instructions);
+    println!("Elapsed:      {:.3} s", elapsed);
+    println!(
+        "Speed:        {:.0} instructions/s ({:.1}x real time)",
+        instructions as f64 / elapsed,
+        args.frames as f64 / 60.0 / elapsed
+    );
+
+    Ok(())
+}
+
+pub fn test(args: TestArgs, context: &Context) -> Result<(), Box<dyn Error>> {
+    let rom = Rom::read(&args.rom)?;
+    if args.compare {
+        return compare_cores(context, &rom, args.machine.settings(), args.frames);
+    }
+    let settings = resolve_settings(context, &rom, args.machine.settings());
+    let mut chip8 = load_machine(&rom, &settings)?;
+
+    run_frames(
+        chip8.as_mut(),
+        args.frames,
+        settings.on_undefined.unwrap_or_default(),
+    )?;
+    let actual = snapshot(chip8.display());
+
+    let Some(path) = args.expect else {
+        print!("{}", actual);
+        return Ok(());
+    };
+
+    let expected = fs::read_to_string(&path)
+        .map_err(|e| format!("could not read '{}': {}", path.display(), e))?;
+
+    if expected.trim_end() != actual.trim_end() {
+        print!("{}", actual);
+        return Err(format!(
+            "screen after {} frames does not match '{}'",
+            args.frames,
+            path.display()
+        )
+        .into());
+    }
+
+    println!("ok: screen matches {}", path.display());
+
+    Ok(())
+}
+
+/// Runs the ROM on the built-in interpreter and on the original one on the
+/// VIP core, and prints both screens when they differ.
+fn compare_cores(
+    context: &Context,
+    rom: &Rom,
+    command_line: Settings,
+    frames: u64,
+) -> Result<(), Box<dyn Error>> {
+    let mut screens = vec![];
+    for core in [Core::Hle, Core::Vip] {
+        let command_line = Settings {
+            core: Some(core),
+            ..command_line.clone()
+        };
+        let settings = resolve_settings(context, rom, command_line);
+        let mut machine = load_machine(rom, &settings)?;
+        run_frames(
+            machine.as_mut(),
+            frames,
+            settings.on_undefined.unwrap_or_default(),
+        )?;
+        screens.push(snapshot(machine.display()));
+    }
+
+    if screens[0] != screens[1] {
+        print!("hle:\n{}\nvip:\n{}", screens[0], screens[1]);
+        return Err(format!(
+            "the screens of the two cores differ after {} frames",
+            frames
+        )
+        .into());
+    }
+
+    println!(
+        "ok: both cores show the same screen after {} frames",
+        frames
+    );
+
+    Ok(())
+}
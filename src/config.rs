@@ -0,0 +1,437 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use toml::{Table, Value};
+
+use crate::{
+    audio::{SoundSink, MAX_TONE, MIN_TONE},
+    filter::FilterMode,
+    font::FontSet,
+    glyphs::RenderMode,
+    graphics::MAX_PIXEL_SIZE,
+    keymap::KeyLayout,
+    machine::Core,
+    memory::{
+        parse_address, parse_memory_size, MemoryMap, Region, MAX_MEMORY_SIZE, MIN_MEMORY_SIZE,
+    },
+    palette::Palette,
+    policy::OpcodePolicy,
+    quirks::{Platform, QuirkOverrides},
+    recording::RecordFormat,
+    screenshot::ImageFormat,
+    stack::MAX_STACK_DEPTH,
+    timing::Timing,
+};
+
+/// Run options that may come from the command line, the configuration file
+/// or both. Unset options fall back to the next source in line.
+#[derive(Clone, Debug, Default)]
+pub struct Settings {
+    pub ipf: Option<u32>,
+    pub timing: Option<Timing>,
+    pub platform: Option<Platform>,
+    pub quirks: Option<Platform>,
+    pub quirk_overrides: QuirkOverrides,
+    pub seed: Option<u64>,
+    pub on_undefined: Option<OpcodePolicy>,
+    pub stack_depth: Option<u16>,
+    pub memory_stack: Option<bool>,
+    pub memory_display: Option<bool>,
+    pub memory_size: Option<usize>,
+    pub load_address: Option<u16>,
+    pub font_address: Option<u16>,
+    pub reserved: Option<Vec<Region>>,
+    pub font: Option<FontSet>,
+    pub font_file: Option<PathBuf>,
+    pub core: Option<Core>,
+    pub interpreter: Option<PathBuf>,
+    pub vip_rom: Option<PathBuf>,
+    pub palette: Option<Palette>,
+    pub keys: Option<KeyLayout>,
+    pub renderer: Option<RenderMode>,
+    pub scale: Option<bool>,
+    pub pixel_size: Option<u16>,
+    pub filter: Option<FilterMode>,
+    pub screenshot_format: Option<ImageFormat>,
+    pub screenshot_dir: Option<PathBuf>,
+    pub record_format: Option<RecordFormat>,
+    pub sound: Option<SoundSink>,
+    pub sound_file: Option<PathBuf>,
+    pub tone: Option<u16>,
+    pub volume: Option<u8>,
+    pub paused: Option<bool>,
+    pub headless: Option<bool>,
+    pub frames: Option<u64>,
+}
+
+impl Settings {
+    /// The memory layout these settings ask for, defaults filling the rest.
+    pub fn memory_map(&self) -> MemoryMap {
+        let defaults = MemoryMap::default();
+
+        MemoryMap {
+            size: self.memory_size.unwrap_or(defaults.size),
+            program_address: self.load_address.unwrap_or(defaults.program_address),
+            font_address: self.font_address.unwrap_or(defaults.font_address),
+            reserved: self.reserved.clone().unwrap_or(defaults.reserved),
+            display_in_memory: self.memory_display.unwrap_or(defaults.display_in_memory),
+        }
+    }
+
+    /// Returns these settings with every option set in `other` replaced.
+    /// Individual quirks only carry over while the quirk preset does.
+    pub fn overridden_by(self, other: Settings) -> Settings {
+        let quirk_overrides = if other.platform.is_some() || other.quirks.is_some() {
+            other.quirk_overrides
+        } else {
+            self.quirk_overrides.overridden_by(other.quirk_overrides)
+        };
+
+        Settings {
+            ipf: other.ipf.or(self.ipf),
+            timing: other.timing.or(self.timing),
+            platform: other.platform.or(self.platform),
+            quirks: other.quirks.or(self.quirks),
+            quirk_overrides,
+            seed: other.seed.or(self.seed),
+            on_undefined: other.on_undefined.or(self.on_undefined),
+            stack_depth: other.stack_depth.or(self.stack_depth),
+            memory_stack: other.memory_stack.or(self.memory_stack),
+            memory_display: other.memory_display.or(self.memory_display),
+            memory_size: other.memory_size.or(self.memory_size),
+            load_address: other.load_address.or(self.load_address),
+            font_address: other.font_address.or(self.font_address),
+            reserved: other.reserved.or(self.reserved),
+            font: other.font.or(self.font),
+            font_file: other.font_file.or(self.font_file),
+            core: other.core.or(self.core),
+            interpreter: other.interpreter.or(self.interpreter),
+            vip_rom: other.vip_rom.or(self.vip_rom),
+            palette: other.palette.or(self.palette),
+            keys: other.keys.or(self.keys),
+            renderer: other.renderer.or(self.renderer),
+            scale: other.scale.or(self.scale),
+            pixel_size: other.pixel_size.or(self.pixel_size),
+            filter: other.filter.or(self.filter),
+            screenshot_format: other.screenshot_format.or(self.screenshot_format),
+            screenshot_dir: other.screenshot_dir.or(self.screenshot_dir),
+            record_format: other.record_format.or(self.record_format),
+            sound: other.sound.or(self.sound),
+            sound_file: other.sound_file.or(self.sound_file),
+            tone: other.tone.or(self.tone),
+            volume: other.volume.or(self.volume),
+            paused: other.paused.or(self.paused),
+            headless: other.headless.or(self.headless),
+            frames: other.frames.or(self.frames),
+        }
+    }
+}
+
+/// The user's configuration file. Top-level keys are defaults for every ROM,
+/// `[rom."<file name>"]` and `[rom."<sha1>"]` tables override them for a
+/// single ROM. `database` names an extra ROM database to read:
+///
+/// ```toml
+/// database = "/home/me/chip-8-database/database/programs.json"
+/// ipf = 15
+/// palette = "green"
+///
+/// [rom."pong.ch8"]
+/// ipf = 7
+/// ```
+#[derive(Default)]
+pub struct Config {
+    pub database: Option<PathBuf>,
+    defaults: Settings,
+    roms: HashMap<String, Settings>,
+    pub warnings: Vec<String>,
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/chip8-emulator`, falling back to `~/.config` and
+    /// `%APPDATA%`.
+    pub fn directory() -> Option<PathBuf> {
+        let base = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .or_else(|| env::var_os("APPDATA").map(PathBuf::from))?;
+
+        Some(base.join("chip8-emulator"))
+    }
+
+    pub fn default_path() -> Option<PathBuf> {
+        Some(Config::directory()?.join("config.toml"))
+    }
+
+    pub fn load(path: &Path) -> Result<Config, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("could not read '{}': {}", path.display(), e))?;
+
+        Config::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse(text: &str) -> Result<Config, String> {
+        let mut table: Table = text.parse().map_err(|e: toml::de::Error| e.to_string())?;
+        let roms = table.remove("rom");
+
+        let mut config = Config::default();
+
+        if let Some(database) = table.remove("database") {
+            let Value::String(path) = database else {
+                return Err("'database' must be the path of a programs.json file".to_string());
+            };
+            config.database = Some(PathBuf::from(path));
+        }
+
+        config.defaults = parse_settings(&table, "the top level", &mut config.warnings)?;
+
+        if let Some(roms) = roms {
+            let Value::Table(roms) = roms else {
+                return Err("'rom' must be a table of per-ROM sections".to_string());
+            };
+
+            for (rom, section) in &roms {
+                let Value::Table(section) = section else {
+                    return Err(format!("[rom.\"{}\"] must be a table", rom));
+                };
+
+                let context = format!("[rom.\"{}\"]", rom);
+                let settings = parse_settings(section, &context, &mut config.warnings)?;
+                config.roms.insert(normalize_key(rom), settings);
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// The settings for a ROM: the defaults, overridden by a section keyed by
+    /// its file name, overridden by a section keyed by its SHA-1 hash.
+    pub fn settings_for(&self, file_name: &str, sha1: &str) -> Settings {
+        let mut settings = self.defaults.clone();
+
+        for key in [file_name, sha1] {
+            if let Some(section) = self.roms.get(&normalize_key(key)) {
+                settings = settings.overridden_by(section.clone());
+            }
+        }
+
+        settings
+    }
+}
+
+// Hashes are matched case-insensitively, file names exactly.
+fn normalize_key(key: &str) -> String {
+    if key.len() == 40 && key.chars().all(|c| c.is_ascii_hexdigit()) {
+        key.to_ascii_lowercase()
+    } else {
+        key.to_string()
+    }
+}
+
+fn parse_settings(
+    table: &Table,
+    context: &str,
+    warnings: &mut Vec<String>,
+) -> Result<Settings, String> {
+    let mut settings = Settings::default();
+
+    for (key, value) in table {
+        let invalid = |expected: &str| format!("'{}' in {} must be {}", key, context, expected);
+
+        match key.as_str() {
+            "ipf" => {
+                settings.ipf = Some(
+                    integer(value, 1, 100_000)
+                        .ok_or_else(|| invalid("a number from 1 to 100000"))?
+                        as u32,
+                )
+            }
+            "timing" => {
+                settings.timing = Some(enumeration(value).ok_or_else(|| invalid("ipf or vip"))?)
+            }
+            "seed" => {
+                settings.seed = Some(
+                    integer(value, 0, i64::MAX).ok_or_else(|| invalid("a positive number"))? as u64,
+                )
+            }
+            "stack_depth" => {
+                settings.stack_depth =
+                    Some(integer(value, 1, MAX_STACK_DEPTH as i64).ok_or_else(|| {
+                        invalid(&format!("a number from 1 to {}", MAX_STACK_DEPTH))
+                    })? as u16)
+            }
+            "memory_stack" => {
+                settings.memory_stack =
+                    Some(value.as_bool().ok_or_else(|| invalid("true or false"))?)
+            }
+            "memory_display" => {
+                settings.memory_display =
+                    Some(value.as_bool().ok_or_else(|| invalid("true or false"))?)
+            }
+            "memory_size" => {
+                let size = match value {
+                    Value::String(text) => parse_memory_size(text).ok(),
+                    _ => integer(value, MIN_MEMORY_SIZE as i64, MAX_MEMORY_SIZE as i64)
+                        .map(|n| n as usize),
+                };
+                settings.memory_size =
+                    Some(size.ok_or_else(|| invalid("a size from 1K to 64K such as \"4K\""))?)
+            }
+            "load_address" => {
+                settings.load_address =
+                    Some(address(value).ok_or_else(|| invalid("an address such as 0x200"))?)
+            }
+            "font_address" => {
+                settings.font_address =
+                    Some(address(value).ok_or_else(|| invalid("an address such as 0x50"))?)
+            }
+            "reserved" => {
+                let regions = value.as_array().and_then(|regions| {
+                    regions
+                        .iter()
+                        .map(|region| region.as_str()?.parse().ok())
+                        .collect::<Option<Vec<Region>>>()
+                });
+                settings.reserved =
+                    Some(regions.ok_or_else(|| {
+                        invalid("a list of address ranges such as [\"0xEA0-0xFFF\"]")
+                    })?)
+            }
+            "font" => {
+                settings.font = Some(
+                    enumeration(value)
+                        .ok_or_else(|| invalid("vip, dream6800, eti660, chip48 or schip"))?,
+                )
+            }
+            "font_file" => {
+                settings.font_file = Some(PathBuf::from(
+                    value.as_str().ok_or_else(|| invalid("a string"))?,
+                ))
+            }
+            "core" => {
+                settings.core = Some(enumeration(value).ok_or_else(|| invalid("hle or vip"))?)
+            }
+            "interpreter" => {
+                settings.interpreter = Some(PathBuf::from(
+                    value.as_str().ok_or_else(|| invalid("a string"))?,
+                ))
+            }
+            "vip_rom" => {
+                settings.vip_rom = Some(PathBuf::from(
+                    value.as_str().ok_or_else(|| invalid("a string"))?,
+                ))
+            }
+            "frames" => {
+                settings.frames = Some(
+                    integer(value, 0, i64::MAX).ok_or_else(|| invalid("a positive number"))? as u64,
+                )
+            }
+            "paused" => {
+                settings.paused = Some(value.as_bool().ok_or_else(|| invalid("true or false"))?)
+            }
+            "scale" => {
+                settings.scale = Some(value.as_bool().ok_or_else(|| invalid("true or false"))?)
+            }
+            "pixel_size" => {
+                settings.pixel_size = Some(
+                    integer(value, 1, MAX_PIXEL_SIZE as i64)
+                        .ok_or_else(|| invalid(&format!("a number from 1 to {}", MAX_PIXEL_SIZE)))?
+                        as u16,
+                )
+            }
+            "filter" => {
+                settings.filter = Some(
+                    enumeration(value).ok_or_else(|| invalid("none, vblank, blend or phosphor"))?,
+                )
+            }
+            "screenshot_format" => {
+                settings.screenshot_format =
+                    Some(enumeration(value).ok_or_else(|| invalid("png, ppm or pbm"))?)
+            }
+            "screenshot_dir" => {
+                settings.screenshot_dir = Some(PathBuf::from(
+                    value.as_str().ok_or_else(|| invalid("a string"))?,
+                ))
+            }
+            "record_format" => {
+                settings.record_format =
+                    Some(enumeration(value).ok_or_else(|| invalid("gif or cast"))?)
+            }
+            "sound" => {
+                settings.sound =
+                    Some(enumeration(value).ok_or_else(|| invalid("off, bell, wav or pcm"))?)
+            }
+            "sound_file" => {
+                settings.sound_file = Some(PathBuf::from(
+                    value.as_str().ok_or_else(|| invalid("a string"))?,
+                ))
+            }
+            "tone" => {
+                settings.tone = Some(integer(value, MIN_TONE as i64, MAX_TONE as i64).ok_or_else(
+                    || invalid(&format!("a number from {} to {}", MIN_TONE, MAX_TONE)),
+                )? as u16)
+            }
+            "volume" => {
+                settings.volume = Some(
+                    integer(value, 0, 100).ok_or_else(|| invalid("a number from 0 to 100"))? as u8,
+                )
+            }
+            "headless" => {
+                settings.headless = Some(value.as_bool().ok_or_else(|| invalid("true or false"))?)
+            }
+            "platform" => {
+                settings.platform =
+                    Some(enumeration(value).ok_or_else(|| invalid("chip8, schip or xochip"))?)
+            }
+            "quirks" => {
+                settings.quirks =
+                    Some(enumeration(value).ok_or_else(|| invalid("chip8, schip or xochip"))?)
+            }
+            "on_undefined" => {
+                settings.on_undefined =
+                    Some(enumeration(value).ok_or_else(|| invalid("halt, break, skip or log"))?)
+            }
+            "keys" => {
+                settings.keys = Some(
+                    enumeration(value)
+                        .ok_or_else(|| invalid("a keyboard layout such as qwerty"))?,
+                )
+            }
+            "renderer" => {
+                settings.renderer = Some(enumeration(value).ok_or_else(|| {
+                    invalid("auto, half-block, quadrant, sextant, braille or ascii")
+                })?)
+            }
+            "palette" => {
+                let name = value.as_str().ok_or_else(|| invalid("a string"))?;
+                settings.palette = Some(
+                    name.parse()
+                        .map_err(|e| format!("'{}' in {}: {}", key, context, e))?,
+                );
+            }
+            _ => warnings.push(format!("unknown key '{}' in {} is ignored", key, context)),
+        }
+    }
+
+    Ok(settings)
+}
+
+fn integer(value: &Value, min: i64, max: i64) -> Option<i64> {
+    value.as_integer().filter(|n| (min..=max).contains(n))
+}
+
+// Addresses are written as TOML integers, usually in hexadecimal, or as
+// strings in the same form as on the command line.
+fn address(value: &Value) -> Option<u16> {
+    match value {
+        Value::String(text) => parse_address(text).ok(),
+        _ => integer(value, 0, u16::MAX as i64).map(|n| n as u16),
+    }
+}
+
+fn enumeration<T: ValueEnum>(value: &Value) -> Option<T> {
+    T::from_str(value.as_str()?, true).ok()
+}
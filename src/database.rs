@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::{
+    config::Settings,
+    font::FontSet,
+    palette::{parse_rgb, Palette},
+    quirks::{Platform, QuirkOverrides},
+};
+
+// The community database, and the entries of the demo programs in roms/.
+const BUNDLED: [&str; 2] = [
+    include_str!("../data/programs.json"),
+    include_str!("../data/demos.json"),
+];
+
+/// A program in the CHIP-8 database. The file format is the `programs.json`
+/// of the community CHIP-8 database, so it can be used as a drop-in
+/// replacement for the bundled one.
+#[derive(Deserialize)]
+pub struct Program {
+    pub title: String,
+    #[serde(default)]
+    pub authors: Vec<String>,
+    pub description: Option<String>,
+    pub release: Option<String>,
+    #[serde(default)]
+    pub roms: HashMap<String, RomEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RomEntry {
+    pub file: Option<String>,
+    #[serde(default)]
+    pub platforms: Vec<String>,
+    #[serde(default)]
+    pub quirky_platforms: HashMap<String, HashMap<String, bool>>,
+    pub tickrate: Option<u32>,
+    pub keys: Option<HashMap<String, u8>>,
+    pub colors: Option<Colors>,
+}
+
+#[derive(Deserialize)]
+pub struct Colors {
+    #[serde(default)]
+    pub pixels: Vec<String>,
+}
+
+pub struct Entry<'a> {
+    pub program: &'a Program,
+    pub rom: &'a RomEntry,
+}
+
+#[derive(Default)]
+pub struct Database {
+    programs: Vec<Program>,
+    index: HashMap<String, usize>,
+}
+
+impl Database {
+    /// The database compiled into the emulator, extended by the entries of
+    /// `extra` when given. Entries from `extra` win when a hash is in both.
+    pub fn load(extra: Option<&Path>) -> Result<Database, String> {
+        let mut database = Database::default();
+        for json in BUNDLED {
+            database
+                .extend(json)
+                .map_err(|e| format!("bundled database: {}", e))?;
+        }
+
+        if let Some(path) = extra {
+            let text = fs::read_to_string(path)
+                .map_err(|e| format!("could not read '{}': {}", path.display(), e))?;
+            database
+                .extend(&text)
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+
+        Ok(database)
+    }
+
+    fn extend(&mut self, json: &str) -> Result<(), serde_json::Error> {
+        let programs: Vec<Program> = serde_json::from_str(json)?;
+
+        for program in programs {
+            for hash in program.roms.keys() {
+                self.index
+                    .insert(hash.to_ascii_lowercase(), self.programs.len());
+            }
+            self.programs.push(program);
+        }
+
+        Ok(())
+    }
+
+    pub fn lookup(&self, sha1: &str) -> Option<Entry<'_>> {
+        let program = &self.programs[*self.index.get(sha1)?];
+        let rom = program
+            .roms
+            .iter()
+            .find(|(hash, _)| hash.eq_ignore_ascii_case(sha1))
+            .map(|(_, rom)| rom)?;
+
+        Some(Entry { program, rom })
+    }
+}
+
+// Maps the database's platform identifiers onto the platforms emulated here.
+fn platform_from_id(id: &str) -> Option<Platform> {
+    match id {
+        "originalChip8" | "hybridVIP" | "modernChip8" | "chip8x" => Some(Platform::Chip8),
+        "chip48" | "superchip1" | "superchip" => Some(Platform::Schip),
+        "xochip" => Some(Platform::Xochip),
+        _ => None,
+    }
+}
+
+// How the platforms behind the database's identifiers differ from the
+// preset they are mapped onto: modern CHIP-8 interpreters neither reset VF
+// in logic instructions nor wait for the display.
+fn quirks_from_id(id: &str) -> QuirkOverrides {
+    match id {
+        "modernChip8" => QuirkOverrides {
+            vf_reset: Some(false),
+            display_wait: Some(false),
+            ..QuirkOverrides::default()
+        },
+        _ => QuirkOverrides::default(),
+    }
+}
+
+// The fonts of the interpreters behind the database's platform identifiers.
+fn font_from_id(id: &str) -> Option<FontSet> {
+    match id {
+        "originalChip8" | "hybridVIP" | "chip8x" => Some(FontSet::Vip),
+        "chip48" => Some(FontSet::Chip48),
+        "superchip1" | "superchip" => Some(FontSet::Schip),
+        _ => None,
+    }
+}
+
+impl Entry<'_> {
+    /// The first platform listed for the ROM that can be emulated, together
+    /// with its identifier in the database.
+    fn platform(&self) -> Option<(&str, Platform)> {
+        self.rom
+            .platforms
+            .iter()
+            .find_map(|id| Some((id.as_str(), platform_from_id(id)?)))
+    }
+
+    pub fn platform_names(&self) -> &[String] {
+        &self.rom.platforms
+    }
+
+    pub fn palette(&self) -> Option<Palette> {
+        let pixels = &self.rom.colors.as_ref()?.pixels;
+        let colors: Option<Vec<_>> = pixels.iter().map(|pixel| parse_rgb(pixel)).collect();
+
+        Palette::from_colors(&colors?)
+    }
+
+    /// Key hints as `(action, CHIP-8 key)` pairs, e.g. `("up", 0x5)`.
+    pub fn key_hints(&self) -> Vec<(&str, u8)> {
+        let mut hints: Vec<(&str, u8)> = self
+            .rom
+            .keys
+            .iter()
+            .flatten()
+            .map(|(action, key)| (action.as_str(), *key))
+            .collect();
+        hints.sort();
+
+        hints
+    }
+
+    fn quirk_overrides(&self, platform_id: &str) -> QuirkOverrides {
+        let mut overrides = quirks_from_id(platform_id);
+
+        for (quirk, value) in self
+            .rom
+            .quirky_platforms
+            .get(platform_id)
+            .into_iter()
+            .flatten()
+        {
+            let value = Some(*value);
+            match quirk.as_str() {
+                "logic" => overrides.vf_reset = value,
+                "shift" => overrides.shifting = value,
+                "memoryLeaveIUnchanged" => overrides.memory_increment = value.map(|v| !v),
+                "jump" => overrides.jumping = value,
+                "wrap" => overrides.clipping = value.map(|v| !v),
+                "vblank" => overrides.display_wait = value,
+                _ => {}
+            }
+        }
+
+        overrides
+    }
+
+    /// The run options recommended by the database for this ROM.
+    pub fn settings(&self) -> Settings {
+        let platform = self.platform();
+
+        Settings {
+            ipf: self.rom.tickrate.filter(|ipf| *ipf > 0),
+            platform: platform.map(|(_, platform)| platform),
+            quirk_overrides: platform
+                .map(|(id, _)| self.quirk_overrides(id))
+                .unwrap_or_default(),
+            palette: self.palette(),
+            font: platform.and_then(|(id, _)| font_from_id(id)),
+            ..Settings::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rom::sha1_hex;
+
+    #[test]
+    fn bundled_roms_resolve_to_their_entries() {
+        let database = Database::load(None).unwrap();
+
+        let keypad = database
+            .lookup(&sha1_hex(include_bytes!("../roms/keypad.ch8")))
+            .unwrap();
+        assert_eq!(keypad.program.title, "Keypad Test");
+        let settings = keypad.settings();
+        assert_eq!(settings.platform, Some(Platform::Chip8));
+        assert_eq!(settings.ipf, Some(15));
+        assert_eq!(settings.font, Some(FontSet::Vip));
+
+        let digits = database
+            .lookup(&sha1_hex(include_bytes!("../roms/digits.ch8")))
+            .unwrap();
+        let settings = digits.settings();
+        assert_eq!(settings.platform, Some(Platform::Schip));
+        assert!(settings.palette.is_some());
+
+        let beep = database
+            .lookup(&sha1_hex(include_bytes!("../roms/beep.ch8")))
+            .unwrap();
+        let settings = beep.settings();
+        assert_eq!(settings.platform, Some(Platform::Xochip));
+        assert_eq!(settings.quirk_overrides.display_wait, Some(false));
+    }
+
+    #[test]
+    fn modern_chip8_turns_off_the_vip_quirks() {
+        let mut database = Database::default();
+        database
+            .extend(
+                r#"[{"title": "Modern", "roms": {"0123": {"platforms": ["modernChip8"],
+                    "quirkyPlatforms": {"modernChip8": {"wrap": true}}}}}]"#,
+            )
+            .unwrap();
+
+        let settings = database.lookup("0123").unwrap().settings();
+        assert_eq!(settings.platform, Some(Platform::Chip8));
+        let quirks = settings.quirk_overrides;
+        assert_eq!(quirks.vf_reset, Some(false));
+        assert_eq!(quirks.display_wait, Some(false));
+        assert_eq!(quirks.clipping, Some(false));
+    }
+
+    #[test]
+    fn unknown_hash_is_not_found() {
+        let database = Database::load(None).unwrap();
+        assert!(database.lookup(&sha1_hex(b"not a rom")).is_none());
+    }
+}
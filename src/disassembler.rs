@@ -0,0 +1,53 @@
+use crate::opcode::Opcode;
+
+/// Turns a single instruction into the mnemonic syntax from Cowgod's
+/// CHIP-8 technical reference, including SCHIP's `LD HF`, which the
+/// assembler reads back, plus `AUDIO` and `PITCH` for the XO-CHIP sound
+/// instructions. Words that do not decode
+/// to an instruction are emitted as `DW` data.
+pub fn disassemble(word: u16) -> String {
+    let opcode: Opcode = word.into();
+    let (x, y) = (opcode.x, opcode.y);
+
+    match (opcode.category, opcode.x, opcode.y, opcode.n) {
+        (0x0, 0x0, 0xE, 0x0) => "CLS".to_string(),
+        (0x0, 0x0, 0xE, 0xE) => "RET".to_string(),
+        (0x0, ..) => format!("SYS {:#05X}", opcode.nnn()),
+        (0x1, ..) => format!("JP {:#05X}", opcode.nnn()),
+        (0x2, ..) => format!("CALL {:#05X}", opcode.nnn()),
+        (0x3, ..) => format!("SE V{:X}, {:#04X}", x, opcode.kk()),
+        (0x4, ..) => format!("SNE V{:X}, {:#04X}", x, opcode.kk()),
+        (0x5, _, _, 0x0) => format!("SE V{:X}, V{:X}", x, y),
+        (0x6, ..) => format!("LD V{:X}, {:#04X}", x, opcode.kk()),
+        (0x7, ..) => format!("ADD V{:X}, {:#04X}", x, opcode.kk()),
+        (0x8, _, _, 0x0) => format!("LD V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x1) => format!("OR V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x2) => format!("AND V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x3) => format!("XOR V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x4) => format!("ADD V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x5) => format!("SUB V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x6) => format!("SHR V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x7) => format!("SUBN V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0xE) => format!("SHL V{:X}, V{:X}", x, y),
+        (0x9, _, _, 0x0) => format!("SNE V{:X}, V{:X}", x, y),
+        (0xA, ..) => format!("LD I, {:#05X}", opcode.nnn()),
+        (0xB, ..) => format!("JP V0, {:#05X}", opcode.nnn()),
+        (0xC, ..) => format!("RND V{:X}, {:#04X}", x, opcode.kk()),
+        (0xD, ..) => format!("DRW V{:X}, V{:X}, {}", x, y, opcode.n),
+        (0xE, _, 0x9, 0xE) => format!("SKP V{:X}", x),
+        (0xE, _, 0xA, 0x1) => format!("SKNP V{:X}", x),
+        (0xF, _, 0x0, 0x7) => format!("LD V{:X}, DT", x),
+        (0xF, _, 0x0, 0xA) => format!("LD V{:X}, K", x),
+        (0xF, _, 0x1, 0x5) => format!("LD DT, V{:X}", x),
+        (0xF, _, 0x1, 0x8) => format!("LD ST, V{:X}", x),
+        (0xF, _, 0x1, 0xE) => format!("ADD I, V{:X}", x),
+        (0xF, _, 0x2, 0x9) => format!("LD F, V{:X}", x),
+        (0xF, _, 0x3, 0x0) => format!("LD HF, V{:X}", x),
+        (0xF, _, 0x3, 0x3) => format!("LD B, V{:X}", x),
+        (0xF, 0x0, 0x0, 0x2) => "AUDIO".to_string(),
+        (0xF, _, 0x3, 0xA) => format!("PITCH V{:X}", x),
+        (0xF, _, 0x5, 0x5) => format!("LD [I], V{:X}", x),
+        (0xF, _, 0x6, 0x5) => format!("LD V{:X}, [I]", x),
+        _ => format!("DW {:#06X}", word),
+    }
+}
@@ -1,11 +1,4 @@
-use std::io::{stdout, Write};
-
-use crossterm::{
-    cursor, queue,
-    style::{self, Color, ResetColor, SetBackgroundColor, SetForegroundColor},
-    terminal, ExecutableCommand,
-};
-
+#[derive(Clone, PartialEq, Eq)]
 pub struct Display {
     width: u16,
     height: u16,
@@ -14,10 +7,6 @@ pub struct Display {
 
 impl Display {
     pub fn new(width: u16, height: u16) -> Display {
-        stdout()
-            .execute(terminal::EnterAlternateScreen)
-            .expect("Could not enter alternate buffer");
-
         Display {
             width,
             height,
@@ -48,50 +37,36 @@ impl Display {
         self.height
     }
 
-    pub fn draw_pixel(&mut self, x: u16, y: u16) {
-        if self.surface[(x + y * self.width) as usize] == 1 {
-            queue!(stdout(), SetForegroundColor(Color::White))
-                .expect("Could not write to the buffer");
-        } else {
-            queue!(stdout(), SetForegroundColor(Color::Black))
-                .expect("Could not write to the buffer");
-        }
-
-        if y < self.height - 1 && self.surface[(x + (y + 1) * self.width) as usize] == 1 {
-            queue!(stdout(), SetBackgroundColor(Color::White))
-                .expect("Could not write to the buffer");
-        } else {
-            queue!(stdout(), SetBackgroundColor(Color::Black))
-                .expect("Could not write to the buffer");
-        }
+    pub fn get_pixel(&self, x: u16, y: u16) -> u8 {
+        self.surface[(x + y * self.width) as usize]
+    }
 
-        let y_coordinate = ((y as f32) / 2.0).floor() as u16;
-        let x_coordinate = x;
+    pub fn set_pixel(&mut self, x: u16, y: u16, value: u8) {
+        self.surface[(x + y * self.width) as usize] = value;
+    }
 
-        queue!(
-            stdout(),
-            cursor::MoveTo(x_coordinate, y_coordinate),
-            cursor::Hide,
-            style::Print("▀"),
-            ResetColor
-        )
-        .expect("Could not write to the buffer");
+    /// The pixels a bit each, row by row with the leftmost pixel of each
+    /// byte in its most significant bit.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.surface
+            .chunks(8)
+            .map(|pixels| pixels.iter().fold(0, |byte, pixel| byte << 1 | pixel))
+            .collect()
     }
 
-    pub fn display(&mut self) {
-        for x in 0..self.width {
-            for y in (0..self.height).step_by(2) {
-                self.draw_pixel(x, y);
+    /// Sets the pixels from bytes laid out as `to_bytes` makes them,
+    /// returning whether any changed.
+    pub fn set_bytes(&mut self, bytes: &[u8]) -> bool {
+        let mut changed = false;
+
+        for (pixels, byte) in self.surface.chunks_mut(8).zip(bytes) {
+            for (bit, pixel) in pixels.iter_mut().enumerate() {
+                let value = byte >> (7 - bit) & 1;
+                changed |= *pixel != value;
+                *pixel = value;
             }
         }
-        stdout().flush().expect("Could not flush stdout");
-    }
-}
 
-impl Drop for Display {
-    fn drop(&mut self) {
-        stdout()
-            .execute(terminal::LeaveAlternateScreen)
-            .expect("Could not leave the alternate buffer");
+        changed
     }
 }
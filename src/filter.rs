@@ -0,0 +1,101 @@
+use clap::ValueEnum;
+
+use crate::{
+    display::Display,
+    palette::{FADE_LEVELS, FADE_PIXEL},
+};
+
+/// How the frames of the machine are turned into what is drawn. Sprites are
+/// erased and drawn again with XOR, which flickers when every change is
+/// shown; the filters hide that.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum FilterMode {
+    /// Draw every change to the display as it happens.
+    #[default]
+    None,
+    /// Draw the display once per frame, at the vertical blank.
+    Vblank,
+    /// Draw each pixel lit in this frame or the one before.
+    Blend,
+    /// Let pixels that go dark fade out over a few frames, like the
+    /// phosphor of a CRT.
+    Phosphor,
+}
+
+/// Sits between the machine's display and the renderer, keeping what the
+/// chosen `FilterMode` needs from earlier frames. Snapshots and headless
+/// runs use the display itself.
+pub struct Filter {
+    mode: FilterMode,
+    previous: Display,
+    // For `Phosphor`, frames left before each pixel has faded out.
+    levels: Vec<u8>,
+    output: Display,
+}
+
+impl Filter {
+    pub fn new(mode: FilterMode, display: &Display) -> Filter {
+        Filter {
+            mode,
+            previous: display.clone(),
+            levels: vec![0; display.width() as usize * display.height() as usize],
+            output: display.clone(),
+        }
+    }
+
+    /// Whether changes are only drawn at the end of a frame, through
+    /// `end_frame`.
+    pub fn at_vblank(&self) -> bool {
+        self.mode != FilterMode::None
+    }
+
+    /// Forgets earlier frames, e.g. after a reset.
+    pub fn clear(&mut self, display: &Display) {
+        *self = Filter::new(self.mode, display);
+    }
+
+    /// Takes the display at the end of a frame and returns whether what is
+    /// drawn changed.
+    pub fn end_frame(&mut self, display: &Display) -> bool {
+        let mut output = display.clone();
+
+        match self.mode {
+            FilterMode::None | FilterMode::Vblank => {}
+            FilterMode::Blend => {
+                for y in 0..display.height() {
+                    for x in 0..display.width() {
+                        let value = display.get_pixel(x, y) | self.previous.get_pixel(x, y);
+                        output.set_pixel(x, y, value);
+                    }
+                }
+            }
+            FilterMode::Phosphor => {
+                for y in 0..display.height() {
+                    for x in 0..display.width() {
+                        let level = &mut self.levels[(y * display.width() + x) as usize];
+                        if display.get_pixel(x, y) != 0 {
+                            *level = FADE_LEVELS + 1;
+                            continue;
+                        }
+
+                        *level = level.saturating_sub(1);
+                        if *level > 0 {
+                            output.set_pixel(x, y, FADE_PIXEL + FADE_LEVELS - *level);
+                        }
+                    }
+                }
+            }
+        }
+
+        self.previous = display.clone();
+        let changed = output != self.output;
+        self.output = output;
+
+        changed
+    }
+
+    /// What to draw, or `None` when the display is drawn as it is.
+    pub fn output(&self) -> Option<&Display> {
+        self.at_vblank().then_some(&self.output)
+    }
+}
@@ -0,0 +1,180 @@
+use std::fs;
+use std::path::Path;
+
+use clap::ValueEnum;
+
+/// Bytes of the small font: sixteen 4x5 glyphs, 0 to F.
+pub const SMALL_FONT_SIZE: usize = 80;
+
+/// Bytes per glyph of the big 8x10 font.
+pub const BIG_GLYPH_SIZE: usize = 10;
+
+/// Hexadecimal fonts of interpreters that differed in glyph shapes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum FontSet {
+    /// The COSMAC VIP interpreter
+    Vip,
+    /// CHIPOS on the DREAM 6800, three pixels wide
+    Dream6800,
+    /// The ETI-660 interpreter, three pixels wide
+    Eti660,
+    /// CHIP-48 on the HP 48, the font most interpreters use today
+    #[default]
+    Chip48,
+    /// SUPER-CHIP 1.1, the CHIP-48 font plus an 8x10 font for the digits
+    Schip,
+}
+
+const VIP: [u8; SMALL_FONT_SIZE] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x60, 0x20, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0x70, 0x10, 0xF0, // 3
+    0xA0, 0xA0, 0xF0, 0x20, 0x20, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x10, 0x10, 0x10, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xF0, 0x50, 0x70, 0x50, 0xF0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xF0, 0x50, 0x50, 0x50, 0xF0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+const DREAM_6800: [u8; SMALL_FONT_SIZE] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x40, 0x40, 0x40, 0x40, 0x40, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0x80, 0xA0, 0xA0, 0xE0, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xE0, 0xA0, 0xC0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+const ETI_660: [u8; SMALL_FONT_SIZE] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x20, 0x20, 0x20, 0x20, 0x20, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0xA0, 0xA0, 0xE0, 0x20, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0x80, 0x80, 0xE0, 0xA0, 0xE0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0x20, 0x20, 0xE0, 0xA0, 0xE0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+const CHIP_48: [u8; SMALL_FONT_SIZE] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+// SUPER-CHIP 1.1 only has big glyphs for the digits.
+const SCHIP_BIG: [u8; 10 * BIG_GLYPH_SIZE] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xE0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+];
+
+/// The glyphs FX29 and FX30 point I at: a small 4x5 font and, for SCHIP,
+/// a big 8x10 one stored right after it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Font {
+    small: [u8; SMALL_FONT_SIZE],
+    big: Vec<u8>,
+}
+
+impl Font {
+    pub fn builtin(set: FontSet) -> Font {
+        let (small, big): (_, &[u8]) = match set {
+            FontSet::Vip => (VIP, &[]),
+            FontSet::Dream6800 => (DREAM_6800, &[]),
+            FontSet::Eti660 => (ETI_660, &[]),
+            FontSet::Chip48 => (CHIP_48, &[]),
+            FontSet::Schip => (CHIP_48, &SCHIP_BIG),
+        };
+
+        Font {
+            small,
+            big: big.to_vec(),
+        }
+    }
+
+    /// Reads a font from a file holding the 80 bytes of the small font,
+    /// optionally followed by up to 16 glyphs of the big font.
+    pub fn read(path: &Path) -> Result<Font, String> {
+        let data =
+            fs::read(path).map_err(|e| format!("could not read '{}': {}", path.display(), e))?;
+        let big_size = data.len().wrapping_sub(SMALL_FONT_SIZE);
+
+        if data.len() < SMALL_FONT_SIZE
+            || big_size % BIG_GLYPH_SIZE != 0
+            || big_size > 16 * BIG_GLYPH_SIZE
+        {
+            return Err(format!(
+                "'{}' is {} bytes, a font is {} bytes for the small glyphs plus {} for each \
+                 of up to 16 big ones",
+                path.display(),
+                data.len(),
+                SMALL_FONT_SIZE,
+                BIG_GLYPH_SIZE
+            ));
+        }
+
+        let (small, big) = data.split_at(SMALL_FONT_SIZE);
+        Ok(Font {
+            small: small.try_into().unwrap(),
+            big: big.to_vec(),
+        })
+    }
+
+    /// The small font followed by the big one, as they are stored in memory.
+    pub fn bytes(&self) -> Vec<u8> {
+        [&self.small[..], &self.big].concat()
+    }
+}
+
+impl Default for Font {
+    fn default() -> Font {
+        Font::builtin(FontSet::default())
+    }
+}
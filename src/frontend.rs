@@ -0,0 +1,834 @@
+use std::error::Error;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{
+    self, Receiver, RecvTimeoutError, Sender, SyncSender, TryRecvError, TrySendError,
+};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossterm::{
+    event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    terminal,
+};
+
+use crate::{
+    audio::Audio,
+    display::Display,
+    filter::{Filter, FilterMode},
+    glyphs::RenderMode,
+    graphics,
+    input::{self, KeyQueue},
+    keymap::KeyLayout,
+    machine::Machine,
+    palette::Palette,
+    policy::OpcodePolicy,
+    recording::{Cast, GifRecorder, RecordFormat},
+    rom::Rom,
+    screenshot::{self, ImageFormat},
+    session::TerminalSession,
+    terminal::TerminalRenderer,
+};
+
+pub const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+// How long the renderer waits for a frame before it looks at the other
+// input events again.
+const INPUT_INTERVAL: Duration = Duration::from_millis(2);
+
+// Fast-forward emulates this many frames per displayed frame, slow motion
+// emulates one frame every this many.
+const SPEED_FACTOR: u64 = 4;
+
+const HELP: &str = "Space  pause/resume    Tab    step one frame
+F5     reset           F6     reload the ROM
+Up     faster (IPF)    Down   slower (IPF)
+Right  fast-forward    Left   slow motion
+F2     status bar      F3     call stack
+F9     record          F12    screenshot
+Esc    quit";
+
+pub struct Options {
+    pub rom: PathBuf,
+    pub palette: Palette,
+    pub keys: KeyLayout,
+    pub renderer: RenderMode,
+    pub scale: bool,
+    pub pixel_size: u16,
+    pub filter: FilterMode,
+    pub screenshot_format: ImageFormat,
+    pub screenshot_dir: PathBuf,
+    pub screenshot_at: Vec<u64>,
+    pub record: Option<PathBuf>,
+    pub record_format: RecordFormat,
+    pub record_input: Option<PathBuf>,
+    pub paused: bool,
+    pub frames: Option<u64>,
+    pub on_undefined: OpcodePolicy,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Speed {
+    Normal,
+    FastForward,
+    SlowMotion,
+}
+
+// How often the measured frame and instruction rates are updated.
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Counts emulated frames and instructions to report the rates actually
+/// achieved, which fall behind the nominal ones on a slow machine.
+struct Rates {
+    since: Instant,
+    frames: u64,
+    instructions: u64,
+    frames_per_second: f64,
+    instructions_per_second: f64,
+}
+
+impl Rates {
+    fn new() -> Rates {
+        Rates {
+            since: Instant::now(),
+            frames: 0,
+            instructions: 0,
+            frames_per_second: 0.0,
+            instructions_per_second: 0.0,
+        }
+    }
+
+    fn update(&mut self) {
+        let elapsed = self.since.elapsed();
+        if elapsed < RATE_WINDOW {
+            return;
+        }
+
+        let seconds = elapsed.as_secs_f64();
+        self.frames_per_second = self.frames as f64 / seconds;
+        self.instructions_per_second = self.instructions as f64 / seconds;
+        self.since = Instant::now();
+        self.frames = 0;
+        self.instructions = 0;
+    }
+}
+
+enum Control {
+    Continue,
+    Quit,
+}
+
+/// What the renderer asks of the emulation thread on behalf of the keys
+/// pressed.
+enum Command {
+    TogglePause,
+    Step,
+    Reset,
+    Reload,
+    Faster,
+    Slower,
+    ToggleSpeed(Speed),
+    ShowCallStack,
+    ToggleRecording,
+    Quit,
+}
+
+/// What the emulation thread tells the renderer, besides the frames.
+enum Notice {
+    /// Text to show below the screen.
+    Message(String),
+    /// A problem to show, and to report again once the terminal is restored.
+    Warning(String),
+    /// A report for once the terminal is restored only.
+    Log(String),
+    Status(String),
+    /// The buzzer started, with the terminal bell as the sound.
+    Bell,
+    /// Recording should go to an asciinema cast, which only the renderer can
+    /// write.
+    StartCast(PathBuf),
+}
+
+/// Runs the machine in the terminal at 60 frames per second until Esc is
+/// pressed or the frame limit is reached.
+///
+/// The machine runs on a thread of its own and sends the frames to draw to
+/// this one, which draws them. A frame the renderer is not ready for
+/// replaces the one waiting before it, so slow terminal output drops frames
+/// instead of holding up the emulation. A third thread reads the terminal:
+/// keypad keys go straight to the machine, the other keys here.
+pub fn run(
+    chip8: &mut dyn Machine,
+    mut audio: Option<Audio>,
+    options: Options,
+) -> Result<(), Box<dyn Error>> {
+    let session = TerminalSession::start()?;
+    let mut log = vec![];
+    let supported = match options.renderer {
+        RenderMode::Kitty => graphics::kitty_supported(),
+        RenderMode::Sixel => graphics::sixel_supported(),
+        _ => true,
+    };
+    let mode = if supported {
+        options.renderer
+    } else {
+        log.push(format!(
+            "the terminal does not seem to support {:?} graphics, half blocks were used",
+            options.renderer
+        ));
+        RenderMode::HalfBlock
+    };
+
+    let (frame_sender, frames) = mpsc::sync_channel(1);
+    let (notice_sender, notices) = mpsc::channel();
+    let (command_sender, commands) = mpsc::channel();
+    let (event_sender, events) = mpsc::channel();
+    let (key_sender, key_inputs) = mpsc::channel();
+    let stop_input = AtomicBool::new(false);
+
+    // The bell is rung by the renderer, written from the emulation thread it
+    // could land inside an escape sequence.
+    if let Some(audio) = &mut audio {
+        let notices = notice_sender.clone();
+        audio.ring_bell_with(Box::new(move || {
+            let _ = notices.send(Notice::Bell);
+        }));
+    }
+
+    let mut frontend = Frontend {
+        renderer: TerminalRenderer::new(options.palette, mode, options.scale, options.pixel_size),
+        screen: chip8.display().clone(),
+        show_status: true,
+        status: String::new(),
+        log,
+        events,
+        frames,
+        notices,
+        commands: command_sender,
+        options: &options,
+    };
+    let mut keys = KeyQueue::new(key_inputs);
+    if options.record_input.is_some() {
+        keys.record();
+    }
+    let emulation = Emulation {
+        filter: Filter::new(options.filter, chip8.display()),
+        gif: None,
+        audio,
+        chip8,
+        paused: options.paused,
+        speed: Speed::Normal,
+        frame: 0,
+        rates: Rates::new(),
+        status: String::new(),
+        pending: None,
+        keys,
+        frames: frame_sender,
+        notices: notice_sender,
+        commands,
+        options: &options,
+    };
+
+    let releases = session.reports_key_releases();
+    let result = thread::scope(|scope| {
+        let input = scope.spawn(|| {
+            input::read(
+                options.keys,
+                releases,
+                key_sender,
+                event_sender,
+                &stop_input,
+            )
+        });
+        let emulation = scope.spawn(move || emulation.run());
+        let result = frontend.run_loop(&session);
+        frontend.send(Command::Quit);
+        stop_input.store(true, Ordering::SeqCst);
+        let read = input
+            .join()
+            .unwrap_or_else(|panic| panic::resume_unwind(panic));
+        let emulated = emulation
+            .join()
+            .unwrap_or_else(|panic| panic::resume_unwind(panic));
+
+        result
+            .and(read.map_err(Into::into))
+            .and(emulated.map_err(Into::into))
+    });
+    frontend.handle_notices();
+    frontend.stop_cast();
+
+    // Messages are held back until the terminal is restored, writing them
+    // while the alternate screen is shown would corrupt the display.
+    drop(session);
+    for message in frontend.log {
+        eprintln!("warning: {}", message);
+    }
+
+    result
+}
+
+/// The emulation thread: runs the machine at 60 frames per second, plays
+/// the sound and records GIFs, taking commands from the renderer.
+struct Emulation<'a> {
+    chip8: &'a mut dyn Machine,
+    options: &'a Options,
+    filter: Filter,
+    gif: Option<GifRecorder>,
+    audio: Option<Audio>,
+    paused: bool,
+    speed: Speed,
+    frame: u64,
+    rates: Rates,
+    status: String,
+    // The frame to draw that did not fit in the channel yet.
+    pending: Option<Display>,
+    keys: KeyQueue,
+    frames: SyncSender<Display>,
+    notices: Sender<Notice>,
+    commands: Receiver<Command>,
+}
+
+impl Emulation<'_> {
+    fn run(mut self) -> Result<(), String> {
+        let result = self.run_loop();
+        self.stop_recording();
+        self.save_movie();
+        if let Some(Err(e)) = self.audio.take().map(Audio::finish) {
+            self.notify(Notice::Log(e));
+        }
+
+        result
+    }
+
+    fn run_loop(&mut self) -> Result<(), String> {
+        let mut tick: u64 = 0;
+        let mut next_frame = Instant::now();
+
+        if self.paused {
+            self.notify(Notice::Message(HELP.to_string()));
+        }
+        if let Some(path) = &self.options.record {
+            self.start_recording(path)?;
+        }
+
+        loop {
+            loop {
+                let command = match self.commands.try_recv() {
+                    Ok(command) => command,
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => return Ok(()),
+                };
+                if let Control::Quit = self.handle_command(command)? {
+                    return Ok(());
+                }
+            }
+
+            if self.paused {
+                self.keys.apply_all(self.chip8, self.frame);
+            } else {
+                let frames = match self.speed {
+                    Speed::Normal => 1,
+                    Speed::FastForward => SPEED_FACTOR,
+                    Speed::SlowMotion => tick.is_multiple_of(SPEED_FACTOR) as u64,
+                };
+
+                for _ in 0..frames {
+                    self.run_frame()?;
+
+                    if self.options.frames.is_some_and(|limit| self.frame >= limit) {
+                        return Ok(());
+                    }
+                    if self.paused {
+                        break;
+                    }
+                }
+            }
+
+            self.send_pending();
+            self.update_status();
+
+            tick += 1;
+            next_frame += FRAME_DURATION;
+            let now = Instant::now();
+            if next_frame > now {
+                thread::sleep(next_frame - now);
+            } else {
+                next_frame = now;
+            }
+        }
+    }
+
+    /// Emulates one frame, applying the policy for instructions that fail.
+    fn run_frame(&mut self) -> Result<(), String> {
+        let instructions = self.chip8.instructions();
+        self.keys.start_frame(self.frame);
+        if self.chip8.frame_position().is_none() {
+            self.keys.apply_at_start(self.chip8);
+        }
+        while !self.chip8.frame_finished() {
+            if let Some(position) = self.chip8.frame_position() {
+                self.keys.apply_until(self.chip8, position);
+            }
+            let result = self.chip8.emulate_cycle();
+
+            if !self.filter.at_vblank() && self.chip8.take_redraw() {
+                self.draw();
+            }
+
+            if let Err(fault) = result {
+                match self.options.on_undefined {
+                    OpcodePolicy::Halt => return Err(fault.to_string()),
+                    OpcodePolicy::Break => {
+                        self.notify(Notice::Message(format!(
+                            "{}\nPress space to skip it and resume.",
+                            fault
+                        )));
+                        self.paused = true;
+                        break;
+                    }
+                    OpcodePolicy::Skip => {}
+                    OpcodePolicy::Log => self.notify(Notice::Log(fault.to_string())),
+                }
+            }
+        }
+        self.chip8.end_frame();
+        self.rates.instructions += self.chip8.instructions() - instructions;
+        if self.filter.at_vblank() {
+            self.chip8.take_redraw();
+            if self.filter.end_frame(self.chip8.display()) {
+                self.draw();
+            }
+        }
+        self.frame += 1;
+        self.rates.frames += 1;
+
+        if let Some(audio) = &mut self.audio {
+            if let Err(e) = audio.frame(self.chip8.buzzing(), self.chip8.audio_pattern()) {
+                self.audio = None;
+                self.notify(Notice::Warning(e));
+            }
+        }
+
+        if let Some(gif) = &mut self.gif {
+            let screen = self.filter.output().unwrap_or(self.chip8.display());
+            if let Err(e) = gif.frame(screen) {
+                self.gif = None;
+                self.notify(Notice::Warning(e));
+            }
+        }
+
+        if self.options.screenshot_at.contains(&self.frame) {
+            let path = screenshot::frame_path(
+                &self.options.screenshot_dir,
+                &self.options.rom,
+                self.frame,
+                self.options.screenshot_format,
+            );
+            let screen = self.filter.output().unwrap_or(self.chip8.display());
+            let message = save_screenshot(screen, self.options, &path);
+            self.notify(Notice::Message(message));
+        }
+
+        Ok(())
+    }
+
+    fn handle_command(&mut self, command: Command) -> Result<Control, String> {
+        match command {
+            Command::TogglePause => {
+                self.paused = !self.paused;
+                let message = if self.paused { HELP } else { "" };
+                self.notify(Notice::Message(message.to_string()));
+            }
+            Command::Step if self.paused => self.run_frame()?,
+            Command::Step => {}
+            Command::Reset => {
+                self.chip8.reset();
+                self.notify(Notice::Message(String::new()));
+            }
+            Command::Reload => self.reload(),
+            Command::Faster => {
+                if let Some(ipf) = self.chip8.instructions_per_frame() {
+                    self.chip8
+                        .set_instructions_per_frame(ipf + (ipf / 10).max(1));
+                }
+            }
+            Command::Slower => {
+                if let Some(ipf) = self.chip8.instructions_per_frame() {
+                    self.chip8
+                        .set_instructions_per_frame(ipf - (ipf / 10).max(1));
+                }
+            }
+            Command::ToggleSpeed(speed) => {
+                self.speed = if self.speed == speed {
+                    Speed::Normal
+                } else {
+                    speed
+                };
+            }
+            Command::ShowCallStack => {
+                let mut text = format!("PC {:#05X}", self.chip8.program_counter());
+                let call_stack = self.chip8.call_stack();
+                if call_stack.is_empty() {
+                    text.push_str(", no subroutine calls in progress");
+                } else {
+                    text.push_str(", call stack:");
+                    for address in call_stack {
+                        text.push_str(&format!("\n  returns to {:#05X}", address));
+                    }
+                }
+                self.notify(Notice::Message(text));
+            }
+            Command::ToggleRecording => {
+                if self.gif.is_some() {
+                    self.stop_recording();
+                } else {
+                    let path = screenshot::timestamped_path(
+                        &self.options.screenshot_dir,
+                        &self.options.rom,
+                        self.options.record_format.extension(),
+                    );
+                    if let Err(e) = self.start_recording(&path) {
+                        self.notify(Notice::Message(e));
+                    }
+                }
+            }
+            Command::Quit => return Ok(Control::Quit),
+        }
+
+        // Only a reset or reload changes the display here, earlier frames
+        // no longer matter then.
+        if self.chip8.take_redraw() {
+            self.filter.clear(self.chip8.display());
+            self.draw();
+        }
+
+        Ok(Control::Continue)
+    }
+
+    // Sends the display to the renderer, as the filter shows it.
+    fn draw(&mut self) {
+        let screen = self.filter.output().unwrap_or(self.chip8.display());
+        self.pending = Some(screen.clone());
+        self.send_pending();
+    }
+
+    // Hands the pending frame to the renderer if it took the last one, and
+    // keeps it for later otherwise, when a newer frame may replace it.
+    fn send_pending(&mut self) {
+        if let Some(screen) = self.pending.take() {
+            if let Err(TrySendError::Full(screen)) = self.frames.try_send(screen) {
+                self.pending = Some(screen);
+            }
+        }
+    }
+
+    fn notify(&self, notice: Notice) {
+        // The renderer only stops listening once it told the emulation to
+        // stop.
+        let _ = self.notices.send(notice);
+    }
+
+    /// Records a GIF here, or has the renderer record a cast.
+    fn start_recording(&mut self, path: &Path) -> Result<(), String> {
+        match RecordFormat::for_path(path) {
+            RecordFormat::Gif => {
+                let screen = self.filter.output().unwrap_or(self.chip8.display());
+                self.gif = Some(GifRecorder::create(
+                    path,
+                    screen,
+                    &self.options.palette,
+                    self.options.pixel_size,
+                )?);
+                self.notify(Notice::Message(recording_message(path)));
+            }
+            RecordFormat::Cast => self.notify(Notice::StartCast(path.to_path_buf())),
+        }
+
+        Ok(())
+    }
+
+    /// Finishes the GIF in progress, if any. Failures are also kept for
+    /// after the terminal is restored, as recording may stop on exit.
+    fn stop_recording(&mut self) {
+        let Some(gif) = self.gif.take() else {
+            return;
+        };
+
+        let path = gif.path().to_path_buf();
+        match gif.finish() {
+            Ok(()) => self.notify(Notice::Message(format!("Saved {}", path.display()))),
+            Err(e) => self.notify(Notice::Warning(e)),
+        }
+    }
+
+    /// Writes the keypad events recorded with --record-input.
+    fn save_movie(&mut self) {
+        let (Some(mut movie), Some(path)) = (self.keys.take_movie(), &self.options.record_input)
+        else {
+            return;
+        };
+
+        match movie.save(path, self.frame) {
+            Ok(()) => self.notify(Notice::Message(format!("Saved {}", path.display()))),
+            Err(e) => self.notify(Notice::Warning(e)),
+        }
+    }
+
+    /// Reads the ROM from disk again and restarts it.
+    fn reload(&mut self) {
+        let loaded =
+            Rom::read(&self.options.rom).and_then(|rom| self.chip8.load_program(&rom.data));
+
+        let message = match loaded {
+            Ok(()) => String::new(),
+            Err(e) => format!("Reload failed: {}", e),
+        };
+        self.notify(Notice::Message(message));
+    }
+
+    fn update_status(&mut self) {
+        self.rates.update();
+        let state = if self.paused { "PAUSED " } else { "RUNNING" };
+        let speed = match self.speed {
+            Speed::Normal => "1x".to_string(),
+            Speed::FastForward => format!("{}x", SPEED_FACTOR),
+            Speed::SlowMotion => format!("1/{}x", SPEED_FACTOR),
+        };
+        let pacing = self.chip8.pacing();
+        let beep = if self.chip8.sound_active() {
+            "♪"
+        } else {
+            " "
+        };
+        let keys: String = self
+            .chip8
+            .held_keys()
+            .iter()
+            .map(|key| format!("{:X}", key))
+            .collect();
+        let rom = self
+            .options
+            .rom
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+
+        let status = format!(
+            "{}  {}  {:>3.0} fps  {:>7.0} ips  {:<10}  {:<4}  {}  keys {}",
+            rom,
+            state,
+            self.rates.frames_per_second,
+            self.rates.instructions_per_second,
+            pacing,
+            speed,
+            beep,
+            keys
+        );
+
+        if status != self.status {
+            self.notify(Notice::Status(status.clone()));
+            self.status = status;
+        }
+    }
+}
+
+/// The render thread: draws the frames it is sent, handles the keys that
+/// control the emulator and owns everything written to the terminal.
+struct Frontend<'a> {
+    options: &'a Options,
+    renderer: TerminalRenderer,
+    // The last frame drawn.
+    screen: Display,
+    show_status: bool,
+    status: String,
+    log: Vec<String>,
+    events: Receiver<Event>,
+    frames: Receiver<Display>,
+    notices: Receiver<Notice>,
+    commands: Sender<Command>,
+}
+
+impl Frontend<'_> {
+    fn run_loop(&mut self, session: &TerminalSession) -> Result<(), Box<dyn Error>> {
+        let (columns, rows) = terminal::size()?;
+        self.resize(columns, rows);
+
+        loop {
+            if session.interrupted() {
+                return Ok(());
+            }
+
+            loop {
+                let event = match self.events.try_recv() {
+                    Ok(event) => event,
+                    Err(TryRecvError::Empty) => break,
+                    // The input thread ended, on an error it returns.
+                    Err(TryRecvError::Disconnected) => return Ok(()),
+                };
+                if let Control::Quit = self.handle_event(event) {
+                    return Ok(());
+                }
+            }
+
+            match self.frames.recv_timeout(INPUT_INTERVAL) {
+                Ok(screen) => {
+                    self.renderer.render(&screen);
+                    self.screen = screen;
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                // The emulation ended, by the frame limit or an error.
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+
+            self.handle_notices();
+        }
+    }
+
+    fn handle_notices(&mut self) {
+        while let Ok(notice) = self.notices.try_recv() {
+            match notice {
+                Notice::Message(text) => self.renderer.show_message(&text),
+                Notice::Warning(text) => {
+                    self.renderer.show_message(&text);
+                    self.log.push(text);
+                }
+                Notice::Log(text) => self.log.push(text),
+                Notice::Status(status) => {
+                    if self.show_status {
+                        self.renderer.show_status(&status);
+                    }
+                    self.status = status;
+                }
+                Notice::Bell => self.renderer.bell(),
+                Notice::StartCast(path) => {
+                    if let Err(e) = self.start_cast(&path) {
+                        self.renderer.show_message(&e);
+                    }
+                }
+            }
+        }
+    }
+
+    fn handle_event(&mut self, event: Event) -> Control {
+        if let Event::Resize(columns, rows) = event {
+            self.resize(columns, rows);
+            return Control::Continue;
+        }
+
+        let Event::Key(KeyEvent {
+            code,
+            modifiers,
+            kind,
+            ..
+        }) = event
+        else {
+            return Control::Continue;
+        };
+
+        // Raw mode turns Ctrl+C into a key press instead of SIGINT.
+        if code == KeyCode::Char('c') && modifiers.contains(KeyModifiers::CONTROL) {
+            return Control::Quit;
+        }
+
+        if kind != KeyEventKind::Press {
+            return Control::Continue;
+        }
+
+        match code {
+            KeyCode::Esc => return Control::Quit,
+            KeyCode::Char(' ') => self.send(Command::TogglePause),
+            KeyCode::Tab => self.send(Command::Step),
+            KeyCode::F(5) => self.send(Command::Reset),
+            KeyCode::F(6) => self.send(Command::Reload),
+            KeyCode::F(12) => {
+                let path = screenshot::timestamped_path(
+                    &self.options.screenshot_dir,
+                    &self.options.rom,
+                    self.options.screenshot_format.extension(),
+                );
+                let message = save_screenshot(&self.screen, self.options, &path);
+                self.renderer.show_message(&message);
+            }
+            KeyCode::F(9) => {
+                if self.renderer.casting() {
+                    self.stop_cast();
+                } else {
+                    self.send(Command::ToggleRecording);
+                }
+            }
+            KeyCode::F(3) => self.send(Command::ShowCallStack),
+            KeyCode::F(2) => {
+                self.show_status = !self.show_status;
+                let status = if self.show_status { &self.status } else { "" };
+                self.renderer.show_status(status);
+            }
+            KeyCode::Up => self.send(Command::Faster),
+            KeyCode::Down => self.send(Command::Slower),
+            KeyCode::Right => self.send(Command::ToggleSpeed(Speed::FastForward)),
+            KeyCode::Left => self.send(Command::ToggleSpeed(Speed::SlowMotion)),
+            _ => {}
+        }
+
+        Control::Continue
+    }
+
+    fn send(&self, command: Command) {
+        // Commands sent after the emulation ended have nothing left to do.
+        let _ = self.commands.send(command);
+    }
+
+    fn start_cast(&mut self, path: &Path) -> Result<(), String> {
+        let (columns, rows) = terminal::size().map_err(|e| e.to_string())?;
+        let cast = Cast::create(path, columns, rows)?;
+        self.renderer.start_cast(&self.screen, cast);
+        self.renderer.show_message(&recording_message(path));
+
+        Ok(())
+    }
+
+    /// Finishes the cast in progress, if any. Failures are also kept for
+    /// after the terminal is restored, as recording may stop on exit.
+    fn stop_cast(&mut self) {
+        let Some(cast) = self.renderer.stop_cast() else {
+            return;
+        };
+
+        let path = cast.path().to_path_buf();
+        match cast.finish() {
+            Ok(()) => self
+                .renderer
+                .show_message(&format!("Saved {}", path.display())),
+            Err(e) => {
+                self.renderer.show_message(&e);
+                self.log.push(e);
+            }
+        }
+    }
+
+    fn resize(&mut self, columns: u16, rows: u16) {
+        self.renderer.resize(&self.screen, columns, rows);
+    }
+}
+
+/// Saves `screen` as drawn, filter included, and returns the message that
+/// reports the result below it.
+fn save_screenshot(screen: &Display, options: &Options, path: &Path) -> String {
+    let result = screenshot::save(
+        screen,
+        &options.palette,
+        options.pixel_size,
+        options.screenshot_format,
+        path,
+    );
+
+    match result {
+        Ok(()) => format!("Saved {}", path.display()),
+        Err(e) => e,
+    }
+}
+
+fn recording_message(path: &Path) -> String {
+    format!("Recording to {}, press F9 to stop.", path.display())
+}
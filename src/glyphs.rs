@@ -0,0 +1,139 @@
+use std::env;
+
+use clap::ValueEnum;
+
+/// How pixels are packed into character cells when drawing in the terminal.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum RenderMode {
+    /// The densest Unicode mode the terminal has room for, or ASCII when the
+    /// locale is not UTF-8.
+    #[default]
+    Auto,
+    /// Upper and lower half blocks, 1x2 pixels per cell.
+    HalfBlock,
+    /// Quadrant blocks, 2x2 pixels per cell.
+    Quadrant,
+    /// Sextant blocks from Unicode 13, 2x3 pixels per cell.
+    Sextant,
+    /// Braille patterns, 2x4 pixels per cell.
+    Braille,
+    /// Plain ASCII characters, 1x2 pixels per cell.
+    Ascii,
+    /// Real pixels through the kitty graphics protocol.
+    Kitty,
+    /// Real pixels as Sixel images.
+    Sixel,
+}
+
+// Tried in order by `Auto`, from the largest pixels to the smallest.
+const UNICODE_MODES: [RenderMode; 4] = [
+    RenderMode::HalfBlock,
+    RenderMode::Quadrant,
+    RenderMode::Sextant,
+    RenderMode::Braille,
+];
+
+const HALF_BLOCKS: [char; 4] = [' ', '▀', '▄', '█'];
+const QUADRANTS: [char; 16] = [
+    ' ', '▘', '▝', '▀', '▖', '▌', '▞', '▛', '▗', '▚', '▐', '▜', '▄', '▙', '▟', '█',
+];
+const ASCII: [char; 4] = [' ', '\'', '.', ':'];
+
+// Bit of each pixel of a 2x4 cell in a Braille pattern, by row and column.
+const BRAILLE_DOTS: [[u8; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+impl RenderMode {
+    /// Resolves `Auto` to the mode with the largest pixels that fits a
+    /// `width` by `height` display, plus `spare_rows` of text, in a terminal
+    /// of `columns` by `rows` cells. Other modes are returned unchanged.
+    pub fn choose(self, width: u16, height: u16, spare_rows: u16, columns: u16, rows: u16) -> Self {
+        if self != RenderMode::Auto {
+            return self;
+        }
+        if !unicode_locale() {
+            return RenderMode::Ascii;
+        }
+
+        UNICODE_MODES
+            .into_iter()
+            .find(|mode| mode.fits(width, height, spare_rows, columns, rows))
+            .unwrap_or(RenderMode::Braille)
+    }
+
+    /// Whether a `width` by `height` display, plus `spare_rows` of text,
+    /// fits a terminal of `columns` by `rows` cells.
+    pub fn fits(self, width: u16, height: u16, spare_rows: u16, columns: u16, rows: u16) -> bool {
+        let (cell_width, cell_height) = self.cell_size();
+        width.div_ceil(cell_width) <= columns && height.div_ceil(cell_height) + spare_rows <= rows
+    }
+
+    /// Whether the mode draws an image instead of characters.
+    pub fn is_graphics(self) -> bool {
+        matches!(self, RenderMode::Kitty | RenderMode::Sixel)
+    }
+
+    /// Pixels per character cell, as width and height.
+    pub fn cell_size(self) -> (u16, u16) {
+        match self {
+            RenderMode::Kitty | RenderMode::Sixel => unreachable!("images are not made of cells"),
+            RenderMode::Auto | RenderMode::HalfBlock | RenderMode::Ascii => (1, 2),
+            RenderMode::Quadrant => (2, 2),
+            RenderMode::Sextant => (2, 3),
+            RenderMode::Braille => (2, 4),
+        }
+    }
+
+    /// The character for a cell whose pixels are set in `bits`, numbered
+    /// row by row from the top left: bit `row * cell width + column`.
+    pub fn glyph(self, bits: u8) -> char {
+        match self {
+            RenderMode::Auto | RenderMode::HalfBlock => HALF_BLOCKS[bits as usize],
+            RenderMode::Ascii => ASCII[bits as usize],
+            RenderMode::Quadrant => QUADRANTS[bits as usize],
+            RenderMode::Sextant => sextant(bits),
+            RenderMode::Kitty | RenderMode::Sixel => unreachable!("images are not made of cells"),
+            RenderMode::Braille => {
+                let mut dots = 0;
+                for (row, columns) in BRAILLE_DOTS.iter().enumerate() {
+                    for (column, dot) in columns.iter().enumerate() {
+                        if bits & (1 << (row * 2 + column)) != 0 {
+                            dots |= dot;
+                        }
+                    }
+                }
+                char::from_u32(0x2800 + dots as u32).unwrap()
+            }
+        }
+    }
+}
+
+// The sextant block starts at U+1FB00 for the pattern with only the top left
+// pixel set. The empty, full, left and right half patterns already existed
+// as other characters and are left out of the block.
+fn sextant(bits: u8) -> char {
+    match bits {
+        0 => ' ',
+        0b010101 => '▌',
+        0b101010 => '▐',
+        0b111111 => '█',
+        _ => {
+            let skipped = (bits > 0b010101) as u32 + (bits > 0b101010) as u32;
+            char::from_u32(0x1FB00 + bits as u32 - 1 - skipped).unwrap()
+        }
+    }
+}
+
+fn unicode_locale() -> bool {
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .into_iter()
+        .find_map(|name| env::var(name).ok().filter(|value| !value.is_empty()));
+
+    match locale {
+        Some(locale) => {
+            let locale = locale.to_ascii_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        }
+        // Without a locale there is no telling, most terminals handle UTF-8.
+        None => true,
+    }
+}
@@ -0,0 +1,180 @@
+use std::env;
+use std::fmt::Write;
+
+use crate::display::Display;
+
+pub const DEFAULT_PIXEL_SIZE: u16 = 8;
+pub const MAX_PIXEL_SIZE: u16 = 64;
+
+// Kitty takes the image in chunks of at most this many base64 bytes.
+const KITTY_CHUNK: usize = 4096;
+
+// The image id used for the screen, so every frame replaces the last one.
+const KITTY_IMAGE_ID: u32 = 8;
+
+/// Guesses whether the terminal speaks the kitty graphics protocol. Asking
+/// the terminal itself would mean reading its reply past crossterm, so this
+/// goes by the variables the terminals that implement it are known to set.
+pub fn kitty_supported() -> bool {
+    let term = env::var("TERM").unwrap_or_default();
+    let program = env::var("TERM_PROGRAM").unwrap_or_default();
+
+    term == "xterm-kitty"
+        || term == "xterm-ghostty"
+        || env::var_os("KITTY_WINDOW_ID").is_some()
+        || matches!(program.as_str(), "WezTerm" | "ghostty")
+}
+
+/// Guesses whether the terminal can show Sixel images, in the same way as
+/// `kitty_supported`.
+pub fn sixel_supported() -> bool {
+    let term = env::var("TERM").unwrap_or_default();
+    let program = env::var("TERM_PROGRAM").unwrap_or_default();
+
+    term.contains("sixel")
+        || term.starts_with("foot")
+        || term.starts_with("mlterm")
+        || term.starts_with("contour")
+        || matches!(program.as_str(), "WezTerm" | "iTerm.app" | "mintty")
+}
+
+/// Draws the display at the cursor with the kitty graphics protocol, every
+/// CHIP-8 pixel as a `pixel_size` square in the colour `colors` has for its
+/// value. The image is sent as a PNG with a palette, which the large squares
+/// of few colours compress to a small fraction of the raw pixels.
+pub fn kitty(display: &Display, colors: &[[u8; 3]], pixel_size: u16) -> String {
+    let (width, height) = (display.width() * pixel_size, display.height() * pixel_size);
+
+    let mut pixels = Vec::with_capacity(width as usize * height as usize);
+    for y in 0..height {
+        for x in 0..width {
+            pixels.push(display.get_pixel(x / pixel_size, y / pixel_size));
+        }
+    }
+
+    let mut image = Vec::new();
+    let mut encoder = png::Encoder::new(&mut image, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(colors.concat());
+    // Writing to memory cannot fail.
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(&pixels).unwrap();
+    writer.finish().unwrap();
+
+    let data = base64(&image);
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(KITTY_CHUNK).collect();
+    let mut out = String::new();
+
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = (i + 1 < chunks.len()) as u8;
+        if i == 0 {
+            // f=100 is PNG, q=2 silences replies, C=1 leaves the cursor
+            // where it is.
+            write!(
+                out,
+                "\x1b_Ga=T,f=100,i={},p=1,q=2,C=1,m={};",
+                KITTY_IMAGE_ID, more
+            )
+            .unwrap();
+        } else {
+            write!(out, "\x1b_Gm={};", more).unwrap();
+        }
+        out.push_str(std::str::from_utf8(chunk).unwrap());
+        out.push_str("\x1b\\");
+    }
+
+    out
+}
+
+/// Removes the images drawn with `kitty`, which clearing the screen leaves
+/// in place.
+pub fn kitty_clear() -> String {
+    format!("\x1b_Ga=d,d=I,i={},q=2\x1b\\", KITTY_IMAGE_ID)
+}
+
+/// Draws the display at the cursor as a Sixel image, every CHIP-8 pixel as a
+/// `pixel_size` square.
+pub fn sixel(display: &Display, colors: &[[u8; 3]], pixel_size: u16) -> String {
+    let (width, height) = (display.width() * pixel_size, display.height() * pixel_size);
+
+    // Raster attributes: square pixels and the image size. Every colour is
+    // drawn, including the background, since unset sixels stay transparent.
+    let mut out = format!("\x1bP0;1;0q\"1;1;{};{}", width, height);
+    for (register, color) in colors.iter().enumerate() {
+        let [r, g, b] = color.map(|c| c as u32 * 100 / 255);
+        write!(out, "#{};2;{};{};{}", register, r, g, b).unwrap();
+    }
+
+    // Each band is six rows of pixels, drawn once per colour in it.
+    for band in (0..height).step_by(6) {
+        for value in 0..colors.len() as u8 {
+            let mut line = Vec::with_capacity(width as usize);
+            let mut used = false;
+
+            for x in 0..width {
+                let mut bits = 0;
+                for dy in 0..6 {
+                    let y = band + dy;
+                    if y < height && display.get_pixel(x / pixel_size, y / pixel_size) == value {
+                        bits |= 1 << dy;
+                    }
+                }
+                used |= bits != 0;
+                line.push(bits);
+            }
+
+            if used {
+                write!(out, "#{}", value).unwrap();
+                push_run_length(&mut out, &line);
+                out.push('$');
+            }
+        }
+        out.push('-');
+    }
+
+    out.push_str("\x1b\\");
+    out
+}
+
+// Writes sixels, repeats of the same one as `!<count><sixel>`.
+fn push_run_length(out: &mut String, line: &[u8]) {
+    let mut i = 0;
+    while i < line.len() {
+        let run = line[i..]
+            .iter()
+            .take_while(|&&bits| bits == line[i])
+            .count();
+        let sixel = (b'?' + line[i]) as char;
+        if run > 3 {
+            write!(out, "!{}{}", run, sixel).unwrap();
+        } else {
+            out.extend(std::iter::repeat_n(sixel, run));
+        }
+        i += run;
+    }
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
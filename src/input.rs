@@ -0,0 +1,216 @@
+use std::collections::VecDeque;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, Sender};
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+use crate::{keymap::KeyLayout, keypad::Key, machine::Machine, movie::Movie};
+
+// How long the input thread waits for an event before it checks whether it
+// should stop or a key is to be released.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// Without release events, a key counts as held this long after it was
+// pressed, long enough for the keyboard to start repeating it, and this
+// long after each repeat.
+const KEY_DELAY: Duration = Duration::from_millis(500);
+const KEY_REPEAT: Duration = Duration::from_millis(100);
+
+/// A keypad key pressed or released, with the time it was read.
+#[derive(Clone, Copy)]
+pub struct KeyInput {
+    key: Key,
+    pressed: bool,
+    at: Instant,
+}
+
+/// Reads the terminal until `stop` is set: keypad keys go to `keypad` as
+/// they are read, every other event to `events`. Ends early when either
+/// receiver is gone. Unless the terminal `reports_releases`, keys are
+/// released once the keyboard stops repeating them.
+pub fn read(
+    keys: KeyLayout,
+    reports_releases: bool,
+    keypad: Sender<KeyInput>,
+    events: Sender<Event>,
+    stop: &AtomicBool,
+) -> io::Result<()> {
+    // Without release events, until when each key held counts as held.
+    let mut held: [Option<(Key, Instant)>; 16] = [None; 16];
+
+    while !stop.load(Ordering::SeqCst) {
+        let now = Instant::now();
+        for hold in held.iter_mut() {
+            if let Some((key, until)) = *hold {
+                if until <= now {
+                    *hold = None;
+                    let input = KeyInput {
+                        key,
+                        pressed: false,
+                        at: now,
+                    };
+                    if keypad.send(input).is_err() {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+
+        if !event::poll(POLL_INTERVAL)? {
+            continue;
+        }
+
+        let event = event::read()?;
+        let at = Instant::now();
+        if let Event::Key(KeyEvent {
+            code: KeyCode::Char(c),
+            modifiers,
+            kind,
+            ..
+        }) = event
+        {
+            // Raw mode turns Ctrl+C into a key press, which quits.
+            let quit = c == 'c' && modifiers.contains(KeyModifiers::CONTROL);
+            if let Some(key) = keys.map(c).filter(|_| !quit) {
+                let pressed = match kind {
+                    KeyEventKind::Press | KeyEventKind::Repeat if !reports_releases => {
+                        // Repeats come as presses too, each renews the hold.
+                        let hold = &mut held[key as usize];
+                        let repeated = hold.is_some();
+                        let delay = if repeated { KEY_REPEAT } else { KEY_DELAY };
+                        *hold = Some((key, at + delay));
+                        if repeated {
+                            continue;
+                        }
+                        true
+                    }
+                    KeyEventKind::Press => true,
+                    KeyEventKind::Release => false,
+                    KeyEventKind::Repeat => continue,
+                };
+                if keypad.send(KeyInput { key, pressed, at }).is_err() {
+                    return Ok(());
+                }
+                continue;
+            }
+        }
+
+        if events.send(event).is_err() {
+            return Ok(());
+        }
+    }
+
+    Ok(())
+}
+
+/// The keypad events read but not yet seen by the machine. Each frame
+/// stands for the real time since the one before, and the events read in
+/// that time are applied in it: at its start, or, for machines that count
+/// cycles, at the instruction the frame had reached when they were read.
+pub struct KeyQueue {
+    inputs: Receiver<KeyInput>,
+    queue: VecDeque<KeyInput>,
+    // The real time the current frame stands for.
+    since: Instant,
+    until: Instant,
+    // The frames run before the current one, and the events applied so far
+    // when they are recorded.
+    frame: u64,
+    movie: Option<Movie>,
+}
+
+impl KeyQueue {
+    pub fn new(inputs: Receiver<KeyInput>) -> KeyQueue {
+        let now = Instant::now();
+        KeyQueue {
+            inputs,
+            queue: VecDeque::new(),
+            since: now,
+            until: now,
+            frame: 0,
+            movie: None,
+        }
+    }
+
+    /// Records the events from now on, with the frame each is applied at.
+    pub fn record(&mut self) {
+        self.movie = Some(Movie::new());
+    }
+
+    /// The events recorded, if any.
+    pub fn take_movie(&mut self) -> Option<Movie> {
+        self.movie.take()
+    }
+
+    /// Starts the frame after `frame` frames, taking in the events read
+    /// since the last one.
+    pub fn start_frame(&mut self, frame: u64) {
+        self.frame = frame;
+        self.since = self.until;
+        self.until = Instant::now();
+        self.queue.extend(self.inputs.try_iter());
+    }
+
+    /// Applies every event read so far, e.g. while paused after `frame`
+    /// frames.
+    pub fn apply_all(&mut self, machine: &mut dyn Machine, frame: u64) {
+        self.frame = frame;
+        self.queue.extend(self.inputs.try_iter());
+        while let Some(input) = self.queue.pop_front() {
+            self.apply(machine, input);
+        }
+    }
+
+    /// Applies the events of the frame at its start, in the order they were
+    /// read. A key released in the same frame it was pressed is released
+    /// in the next one, with the events after it, so that the program
+    /// sees it held.
+    pub fn apply_at_start(&mut self, machine: &mut dyn Machine) {
+        let mut pressed = [false; 16];
+        while let Some(&input) = self.queue.front() {
+            if !input.pressed && pressed[input.key as usize] {
+                break;
+            }
+            pressed[input.key as usize] |= input.pressed;
+            self.queue.pop_front();
+            self.apply(machine, input);
+        }
+    }
+
+    /// Applies the events read up to `position` into the frame, from 0 at
+    /// its start to 1 at its end.
+    pub fn apply_until(&mut self, machine: &mut dyn Machine, position: f64) {
+        while let Some(&input) = self.queue.front() {
+            if self.position(input.at) > position {
+                break;
+            }
+            self.queue.pop_front();
+            self.apply(machine, input);
+        }
+    }
+
+    // How far into the frame `at` is. Events left over from earlier frames
+    // are at its start.
+    fn position(&self, at: Instant) -> f64 {
+        let span = self.until - self.since;
+        if span.is_zero() {
+            return 0.0;
+        }
+
+        let offset = at.saturating_duration_since(self.since);
+        (offset.as_secs_f64() / span.as_secs_f64()).min(1.0)
+    }
+
+    fn apply(&mut self, machine: &mut dyn Machine, input: KeyInput) {
+        if input.pressed {
+            machine.handle_key_down(input.key);
+        } else {
+            machine.handle_key_up(input.key);
+        }
+        if let Some(movie) = &mut self.movie {
+            movie.record(self.frame, input.key, input.pressed);
+        }
+    }
+}
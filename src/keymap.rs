@@ -0,0 +1,60 @@
+use clap::ValueEnum;
+
+use crate::keypad::Key;
+
+// The CHIP-8 hex keypad, row by row, as it is laid out on the original
+// COSMAC VIP. Each keyboard layout maps the same physical 4x4 block onto it.
+const KEYPAD: [[Key; 4]; 4] = [
+    [Key::Key1, Key::Key2, Key::Key3, Key::KeyC],
+    [Key::Key4, Key::Key5, Key::Key6, Key::KeyD],
+    [Key::Key7, Key::Key8, Key::Key9, Key::KeyE],
+    [Key::KeyA, Key::Key0, Key::KeyB, Key::KeyF],
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum KeyLayout {
+    #[default]
+    Qwerty,
+    Qwertz,
+    Azerty,
+    Dvorak,
+    Colemak,
+}
+
+impl KeyLayout {
+    fn rows(&self) -> [&'static str; 4] {
+        match self {
+            KeyLayout::Qwerty => ["1234", "qwer", "asdf", "zxcv"],
+            KeyLayout::Qwertz => ["1234", "qwer", "asdf", "yxcv"],
+            // The digits are shifted on AZERTY keyboards.
+            KeyLayout::Azerty => ["&é\"'", "azer", "qsdf", "wxcv"],
+            KeyLayout::Dvorak => ["1234", "',.p", "aoeu", ";qjk"],
+            KeyLayout::Colemak => ["1234", "qwfp", "arst", "zxcv"],
+        }
+    }
+
+    pub fn map(&self, c: char) -> Option<Key> {
+        let c = c.to_ascii_lowercase();
+
+        for (row, keys) in self.rows().iter().enumerate() {
+            if let Some(col) = keys.chars().position(|k| k == c) {
+                return Some(KEYPAD[row][col]);
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layouts_cover_the_same_block_of_keys() {
+        assert_eq!(KeyLayout::Azerty.map('&'), Some(Key::Key1));
+        assert_eq!(KeyLayout::Azerty.map('\''), Some(Key::KeyC));
+        assert_eq!(KeyLayout::Colemak.map('v'), Some(Key::KeyF));
+        assert_eq!(KeyLayout::Colemak.map('d'), None);
+    }
+}
@@ -1,4 +1,4 @@
-#[derive(Clone)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Key {
     Key0 = 0,
     Key1,
@@ -18,6 +18,28 @@ pub enum Key {
     KeyF,
 }
 
+impl Key {
+    /// Every key, in the order of their values.
+    pub const ALL: [Key; 16] = [
+        Key::Key0,
+        Key::Key1,
+        Key::Key2,
+        Key::Key3,
+        Key::Key4,
+        Key::Key5,
+        Key::Key6,
+        Key::Key7,
+        Key::Key8,
+        Key::Key9,
+        Key::KeyA,
+        Key::KeyB,
+        Key::KeyC,
+        Key::KeyD,
+        Key::KeyE,
+        Key::KeyF,
+    ];
+}
+
 pub struct Keypad {
     keys: [bool; 16],
     save_key: Option<u8>,
@@ -47,11 +69,9 @@ impl Keypad {
         let mut key: Option<u8> = None;
         if self.save_key.is_none() {
             self.save_key = self.get_any_key_down();
-        } else {
-            if !self.is_key_down(self.save_key.unwrap() as u8) {
-                key = self.save_key;
-                self.save_key = None;
-            }
+        } else if !self.is_key_down(self.save_key.unwrap()) {
+            key = self.save_key;
+            self.save_key = None;
         }
         key
     }
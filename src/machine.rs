@@ -0,0 +1,86 @@
+use clap::ValueEnum;
+
+use crate::{audio::Pattern, display::Display, keypad::Key, processor::Fault};
+
+/// Which emulator runs the program.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Core {
+    /// The built-in CHIP-8 interpreter
+    #[default]
+    Hle,
+    /// An RCA 1802 running the original interpreter on an emulated COSMAC
+    /// VIP, given with --interpreter
+    Vip,
+}
+
+/// What the frontends need of an emulated machine: it runs frame by frame,
+/// shows a 64x32 display and takes the 16 keys of the hex keypad. The
+/// terminal frontend runs it on a thread of its own.
+pub trait Machine: Send {
+    fn display(&self) -> &Display;
+
+    /// Returns whether the display changed since the last call.
+    fn take_redraw(&mut self) -> bool;
+
+    /// Whether this frame has had all the time it gets.
+    fn frame_finished(&self) -> bool;
+
+    /// Runs the next step of the frame: an instruction, or whatever else
+    /// takes the machine's time. An instruction that cannot be executed is
+    /// skipped and reported; what happens next is up to the caller.
+    fn emulate_cycle(&mut self) -> Result<(), Fault>;
+
+    /// How far the machine is into the frame, from 0 to 1, when it counts
+    /// the cycles instructions take, so that keys can be pressed at the
+    /// instruction they would have been on the real machine. `None` when a
+    /// frame is a number of instructions.
+    fn frame_position(&self) -> Option<f64> {
+        None
+    }
+
+    /// Marks the end of a 60 Hz frame.
+    fn end_frame(&mut self);
+
+    /// Instructions executed since the machine was created.
+    fn instructions(&self) -> u64;
+
+    /// Whether the buzzer sounded in the frame `end_frame` last ended.
+    fn buzzing(&self) -> bool;
+
+    /// Whether the buzzer sounds now.
+    fn sound_active(&self) -> bool;
+
+    /// The XO-CHIP audio pattern the buzzer plays instead of its tone.
+    fn audio_pattern(&self) -> Option<Pattern> {
+        None
+    }
+
+    fn handle_key_down(&mut self, key: Key);
+
+    fn handle_key_up(&mut self, key: Key);
+
+    /// The keypad keys currently held down, in ascending order.
+    fn held_keys(&self) -> Vec<u8>;
+
+    /// Puts the machine back in the state it was created in.
+    fn reset(&mut self);
+
+    /// Replaces the program, e.g. after the ROM changed on disk, and
+    /// resets the machine.
+    fn load_program(&mut self, program: &[u8]) -> Result<(), String>;
+
+    /// The address of the next CHIP-8 instruction.
+    fn program_counter(&self) -> u16;
+
+    /// Return addresses on the stack, the innermost call first.
+    fn call_stack(&self) -> Vec<u16>;
+
+    /// Instructions per frame, or `None` when the machine keeps its own
+    /// pace.
+    fn instructions_per_frame(&self) -> Option<u32>;
+
+    fn set_instructions_per_frame(&mut self, instructions_per_frame: u32);
+
+    /// How the speed is set, for the status bar.
+    fn pacing(&self) -> String;
+}
@@ -1,18 +1,278 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::font::{Font, SMALL_FONT_SIZE};
+
+pub const MEMORY_SIZE: usize = 4096;
+pub const MIN_MEMORY_SIZE: usize = 1024;
+pub const MAX_MEMORY_SIZE: usize = 65536;
+pub const FONT_ADDRESS: u16 = 0x50;
+pub const PROGRAM_ADDRESS: u16 = 0x200;
+
+/// Bytes of a 64x32 display kept in memory, a bit per pixel.
+pub const DISPLAY_BYTES: usize = 256;
+
+/// A range of addresses, both ends included, written `0xEA0-0xFFF`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl Region {
+    fn overlaps(&self, start: usize, length: usize) -> bool {
+        length > 0 && start <= self.end as usize && (self.start as usize) < start + length
+    }
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Region, String> {
+        let (start, end) = text
+            .split_once('-')
+            .ok_or_else(|| format!("'{}' is not a range such as 0xEA0-0xFFF", text))?;
+        let region = Region {
+            start: parse_address(start.trim())?,
+            end: parse_address(end.trim())?,
+        };
+
+        if region.start > region.end {
+            return Err(format!("'{}' ends before it starts", text));
+        }
+        Ok(region)
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#05X}-{:#05X}", self.start, self.end)
+    }
+}
+
+/// Parses an address, in hexadecimal with a `0x` prefix or in decimal.
+pub fn parse_address(text: &str) -> Result<u16, String> {
+    let parsed = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+
+    parsed.map_err(|_| format!("'{}' is not an address such as 0x200", text))
+}
+
+/// Parses a memory size in bytes, with an optional `K` for kilobytes.
+pub fn parse_memory_size(text: &str) -> Result<usize, String> {
+    let error = || {
+        format!(
+            "'{}' is not a memory size from {}K to {}K, such as 4K",
+            text,
+            MIN_MEMORY_SIZE / 1024,
+            MAX_MEMORY_SIZE / 1024
+        )
+    };
+    let size = match text.strip_suffix(['K', 'k']) {
+        Some(kilobytes) => kilobytes.parse::<usize>().map_err(|_| error())? * 1024,
+        None => text.parse().map_err(|_| error())?,
+    };
+
+    if !(MIN_MEMORY_SIZE..=MAX_MEMORY_SIZE).contains(&size) {
+        return Err(error());
+    }
+    Ok(size)
+}
+
+/// How much memory there is and where the interpreter puts things in it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryMap {
+    pub size: usize,
+    pub program_address: u16,
+    pub font_address: u16,
+    /// Regions the interpreter keeps for itself, which programs may not be
+    /// loaded over.
+    pub reserved: Vec<Region>,
+    /// Whether the display is kept in the last 256 bytes of memory, at
+    /// 0xF00 with 4K, like on the VIP.
+    pub display_in_memory: bool,
+}
+
+impl Default for MemoryMap {
+    fn default() -> MemoryMap {
+        MemoryMap {
+            size: MEMORY_SIZE,
+            program_address: PROGRAM_ADDRESS,
+            font_address: FONT_ADDRESS,
+            reserved: vec![],
+            display_in_memory: false,
+        }
+    }
+}
+
+impl MemoryMap {
+    /// The region the display is kept in, when it is in memory.
+    pub fn display_region(&self) -> Option<Region> {
+        self.display_in_memory.then(|| Region {
+            start: (self.size - DISPLAY_BYTES) as u16,
+            end: (self.size - 1) as u16,
+        })
+    }
+
+    /// Where the big font is stored, right after the small one.
+    pub fn big_font_address(&self) -> u16 {
+        self.font_address.wrapping_add(SMALL_FONT_SIZE as u16)
+    }
+
+    /// Checks that the program address and the reserved regions lie within
+    /// memory.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.program_address as usize >= self.size {
+            return Err(format!(
+                "the program address {:#05X} is outside the {} bytes of memory",
+                self.program_address, self.size
+            ));
+        }
+        if let Some(region) = self
+            .reserved
+            .iter()
+            .find(|region| region.end as usize >= self.size)
+        {
+            return Err(format!(
+                "the reserved region {} is outside the {} bytes of memory",
+                region, self.size
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
 pub struct Memory {
-    pub data: [u8; 4096],
+    data: Vec<u8>,
+    map: MemoryMap,
+    font: Font,
+    // Whether `set` changed the display since `take_display_written`.
+    display_written: bool,
 }
 
 impl Memory {
+    /// Memory laid out as `map` says, with `font` and `program` loaded. The
+    /// program may not cover the font, a reserved region or the display.
+    pub fn with_program(map: MemoryMap, font: Font, program: &[u8]) -> Result<Memory, String> {
+        map.validate()?;
+        let start = map.program_address as usize;
+        let font_bytes = font.bytes();
+
+        if map.font_address as usize + font_bytes.len() > map.size {
+            return Err(format!(
+                "the font at {:#05X} does not fit in the {} bytes of memory",
+                map.font_address, map.size
+            ));
+        }
+        let font_region = Region {
+            start: map.font_address,
+            end: (map.font_address as usize + font_bytes.len() - 1) as u16,
+        };
+        if font_region.overlaps(start, program.len()) {
+            return Err(format!(
+                "the program would cover the font at {}",
+                font_region
+            ));
+        }
+        if let Some(region) = map
+            .reserved
+            .iter()
+            .find(|region| region.overlaps(start, program.len()))
+        {
+            return Err(format!(
+                "the program would cover the reserved region {}",
+                region
+            ));
+        }
+        if let Some(region) = map.display_region() {
+            if region.overlaps(start, program.len())
+                || region.overlaps(font_region.start as usize, font_bytes.len())
+            {
+                return Err(format!(
+                    "the program or font would cover the display at {}",
+                    region
+                ));
+            }
+        }
+
+        let mut memory = Memory {
+            data: vec![0; map.size],
+            map,
+            font,
+            display_written: false,
+        };
+        memory.load(memory.map.font_address, &font_bytes)?;
+        memory.load(memory.map.program_address, program)?;
+
+        Ok(memory)
+    }
+
+    /// Memory laid out and with the font as this one, with `program` loaded
+    /// instead, e.g. after the ROM changed on disk.
+    pub fn with_other_program(&self, program: &[u8]) -> Result<Memory, String> {
+        Memory::with_program(self.map.clone(), self.font.clone(), program)
+    }
+
+    pub fn map(&self) -> &MemoryMap {
+        &self.map
+    }
+
+    /// Copies `bytes` into memory starting at `location`, returning an error
+    /// when they do not fit.
+    pub fn load(&mut self, location: u16, bytes: &[u8]) -> Result<(), String> {
+        let start = usize::from(location);
+        let available = self.data.len().saturating_sub(start);
+
+        if bytes.len() > available {
+            return Err(format!(
+                "{} bytes do not fit at {:#05X}, only {} bytes are available",
+                bytes.len(),
+                location,
+                available
+            ));
+        }
+
+        self.data[start..(start + bytes.len())].clone_from_slice(bytes);
+
+        Ok(())
+    }
+
     pub fn get_as_u16(&self, location: u16) -> u16 {
-        u16::from(self.data[usize::from(location)]) << 8
-            | u16::from(self.data[usize::from(location + 1)])
+        u16::from(self.get_as_u8(location)) << 8
+            | u16::from(self.get_as_u8(location.wrapping_add(1)))
     }
 
     pub fn get_as_u8(&self, location: u16) -> u8 {
-        self.data[usize::from(location)]
+        self.data[self.index(location)]
     }
 
     pub fn set(&mut self, location: u16, data: u8) {
-        self.data[usize::from(location)] = data;
+        let index = self.index(location);
+        self.data[index] = data;
+
+        if let Some(region) = self.map.display_region() {
+            self.display_written |= region.overlaps(index, 1);
+        }
+    }
+
+    /// The display, when it is kept in memory.
+    pub fn display_bytes(&self) -> Option<&[u8]> {
+        let region = self.map.display_region()?;
+        Some(&self.data[region.start as usize..=region.end as usize])
+    }
+
+    /// Returns whether `set` wrote to the display since the last call.
+    pub fn take_display_written(&mut self) -> bool {
+        std::mem::take(&mut self.display_written)
+    }
+
+    // Addresses past the end of memory wrap around to its start, as they
+    // did on machines with less memory than addresses.
+    fn index(&self, location: u16) -> usize {
+        usize::from(location) % self.data.len()
     }
 }
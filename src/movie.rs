@@ -0,0 +1,156 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use crate::{keypad::Key, machine::Machine};
+
+const HEADER: &str = "\
+# CHIP-8 input movie: one line per keypad event, \"FRAME KEY down\" or
+# \"FRAME KEY up\", applied once FRAME frames have run. \"FRAME end\" is
+# where the recording stopped.
+";
+
+/// Keypad events by the frame they happen at, recorded in a terminal run
+/// and replayed in a headless one.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Movie {
+    events: Vec<MovieEvent>,
+    // The frames the recording lasted.
+    length: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct MovieEvent {
+    frame: u64,
+    key: Key,
+    pressed: bool,
+}
+
+impl Movie {
+    pub fn new() -> Movie {
+        Movie::default()
+    }
+
+    pub fn read(path: &Path) -> Result<Movie, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("could not read '{}': {}", path.display(), e))?;
+        Movie::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn parse(text: &str) -> Result<Movie, String> {
+        let mut movie = Movie::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |what: &str| format!("line {}: {}", index + 1, what);
+
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let frame: u64 = words[0]
+                .parse()
+                .map_err(|_| invalid("expected a frame number"))?;
+            if frame < movie.length {
+                return Err(invalid("events must be in the order of their frames"));
+            }
+            let pressed = match words[1..] {
+                ["end"] => {
+                    movie.length = frame;
+                    continue;
+                }
+                [_, "down"] => true,
+                [_, "up"] => false,
+                _ => return Err(invalid("expected \"FRAME KEY down|up\" or \"FRAME end\"")),
+            };
+            let key = u8::from_str_radix(words[1], 16)
+                .ok()
+                .and_then(|key| Key::ALL.get(key as usize))
+                .ok_or_else(|| invalid("expected a key from 0 to F"))?;
+
+            movie.record(frame, *key, pressed);
+        }
+
+        Ok(movie)
+    }
+
+    /// Adds an event at `frame`, which is no earlier than the last one.
+    pub fn record(&mut self, frame: u64, key: Key, pressed: bool) {
+        self.events.push(MovieEvent {
+            frame,
+            key,
+            pressed,
+        });
+        self.length = self.length.max(frame);
+    }
+
+    /// How many frames the movie lasts.
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+
+    /// Writes the movie, which ends after `frames` frames.
+    pub fn save(&mut self, path: &Path, frames: u64) -> Result<(), String> {
+        self.length = self.length.max(frames);
+        fs::write(path, self.to_text())
+            .map_err(|e| format!("Could not write {}: {}", path.display(), e))
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = HEADER.to_string();
+        for event in &self.events {
+            let state = if event.pressed { "down" } else { "up" };
+            let _ = writeln!(text, "{} {:X} {}", event.frame, event.key as u8, state);
+        }
+        let _ = writeln!(text, "{} end", self.length);
+
+        text
+    }
+
+    /// Applies the events at `frame` to the machine.
+    pub fn replay(&self, frame: u64, machine: &mut dyn Machine) {
+        let start = self.events.partition_point(|event| event.frame < frame);
+        for event in &self.events[start..] {
+            if event.frame != frame {
+                break;
+            }
+            if event.pressed {
+                machine.handle_key_down(event.key);
+            } else {
+                machine.handle_key_up(event.key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_movies_read_back() {
+        let mut movie = Movie::new();
+        movie.record(0, Key::Key5, true);
+        movie.record(12, Key::KeyA, true);
+        movie.record(12, Key::Key5, false);
+        movie.length = 90;
+
+        assert_eq!(Movie::parse(&movie.to_text()), Ok(movie));
+    }
+
+    #[test]
+    fn bad_lines_are_reported() {
+        assert_eq!(
+            Movie::parse("4 1 down\n3 1 up\n"),
+            Err("line 2: events must be in the order of their frames".to_string())
+        );
+        assert_eq!(
+            Movie::parse("# keys\n\n4 G down\n"),
+            Err("line 3: expected a key from 0 to F".to_string())
+        );
+        assert_eq!(
+            Movie::parse("4 1 held\n"),
+            Err("line 1: expected \"FRAME KEY down|up\" or \"FRAME end\"".to_string())
+        );
+    }
+}
@@ -1,3 +1,4 @@
+#[derive(Clone, Copy)]
 pub struct Opcode {
     pub category: u8,
     pub x: usize,
@@ -13,6 +14,94 @@ impl Opcode {
     pub fn nnn(&self) -> u16 {
         (self.x << 8) as u16 | (self.y << 4) as u16 | u16::from(self.n)
     }
+
+    /// The instruction the opcode stands for, if the interpreter has one.
+    pub fn decode(&self) -> Option<Instruction> {
+        let instruction = match (self.category, self.x, self.y, self.n) {
+            (0x0, 0x0, 0xE, 0x0) => Instruction::Cls,
+            (0x0, 0x0, 0xE, 0xE) => Instruction::Ret,
+            (0x1, ..) => Instruction::Jp,
+            (0x2, ..) => Instruction::Call,
+            (0x3, ..) => Instruction::SeByte,
+            (0x4, ..) => Instruction::SneByte,
+            (0x5, _, _, 0x0) => Instruction::SeReg,
+            (0x6, ..) => Instruction::LdByte,
+            (0x7, ..) => Instruction::AddByte,
+            (0x8, _, _, 0x0) => Instruction::LdReg,
+            (0x8, _, _, 0x1) => Instruction::Or,
+            (0x8, _, _, 0x2) => Instruction::And,
+            (0x8, _, _, 0x3) => Instruction::Xor,
+            (0x8, _, _, 0x4) => Instruction::AddReg,
+            (0x8, _, _, 0x5) => Instruction::Sub,
+            (0x8, _, _, 0x6) => Instruction::Shr,
+            (0x8, _, _, 0x7) => Instruction::Subn,
+            (0x8, _, _, 0xE) => Instruction::Shl,
+            (0x9, _, _, 0x0) => Instruction::SneReg,
+            (0xA, ..) => Instruction::LdI,
+            (0xB, ..) => Instruction::JpV0,
+            (0xC, ..) => Instruction::Rnd,
+            (0xD, ..) => Instruction::Drw,
+            (0xE, _, 0x9, 0xE) => Instruction::Skp,
+            (0xE, _, 0xA, 0x1) => Instruction::Sknp,
+            (0xF, _, 0x0, 0x7) => Instruction::LdVxDt,
+            (0xF, _, 0x0, 0xA) => Instruction::LdVxK,
+            (0xF, _, 0x1, 0x5) => Instruction::LdDtVx,
+            (0xF, _, 0x1, 0x8) => Instruction::LdStVx,
+            (0xF, _, 0x1, 0xE) => Instruction::AddI,
+            (0xF, _, 0x2, 0x9) => Instruction::LdF,
+            (0xF, _, 0x3, 0x0) => Instruction::LdHf,
+            (0xF, _, 0x3, 0x3) => Instruction::LdB,
+            (0xF, 0x0, 0x0, 0x2) => Instruction::Audio,
+            (0xF, _, 0x3, 0xA) => Instruction::Pitch,
+            (0xF, _, 0x5, 0x5) => Instruction::Store,
+            (0xF, _, 0x6, 0x5) => Instruction::Load,
+            _ => return None,
+        };
+
+        Some(instruction)
+    }
+}
+
+/// The instructions the interpreter executes, named after their mnemonics.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    Cls,
+    Ret,
+    Jp,
+    Call,
+    SeByte,
+    SneByte,
+    SeReg,
+    LdByte,
+    AddByte,
+    LdReg,
+    Or,
+    And,
+    Xor,
+    AddReg,
+    Sub,
+    Shr,
+    Subn,
+    Shl,
+    SneReg,
+    LdI,
+    JpV0,
+    Rnd,
+    Drw,
+    Skp,
+    Sknp,
+    LdVxDt,
+    LdVxK,
+    LdDtVx,
+    LdStVx,
+    AddI,
+    LdF,
+    LdHf,
+    LdB,
+    Audio,
+    Pitch,
+    Store,
+    Load,
 }
 
 impl From<u16> for Opcode {
@@ -0,0 +1,260 @@
+use std::env;
+use std::str::FromStr;
+
+use crossterm::style::Color;
+
+pub const PRESETS: [&str; 7] = [
+    "mono", "inverted", "green", "amber", "lcd", "octo", "gameboy",
+];
+
+/// Pixel value of the brightest fading shade, see `Palette::pixel_colors`.
+pub const FADE_PIXEL: u8 = 4;
+pub const FADE_LEVELS: u8 = 4;
+
+/// The colours pixels are drawn in, indexed by pixel value: the background,
+/// the first XO-CHIP bitplane, the second bitplane and both bitplanes.
+/// Two-colour palettes draw every lit pixel in the foreground colour.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette {
+    pub colors: [Color; 4],
+}
+
+impl Palette {
+    pub fn two_color(foreground: Color, background: Color) -> Palette {
+        Palette {
+            colors: [background, foreground, foreground, foreground],
+        }
+    }
+
+    pub fn named(name: &str) -> Option<Palette> {
+        let palette = match name {
+            "mono" => Palette::two_color(Color::White, Color::Black),
+            "inverted" => Palette::two_color(Color::Black, Color::White),
+            "green" => Palette::two_color(rgb(0x33FF66), rgb(0x0A1A0A)),
+            "amber" => Palette::two_color(rgb(0xFFB000), rgb(0x1A1000)),
+            "lcd" => Palette::two_color(rgb(0x43523D), rgb(0xC7F0D8)),
+            // The default colours of the Octo XO-CHIP environment.
+            "octo" => Palette {
+                colors: [rgb(0x996600), rgb(0xFFCC00), rgb(0xFF6600), rgb(0x662200)],
+            },
+            "gameboy" => Palette {
+                colors: [rgb(0x9BBC0F), rgb(0x0F380F), rgb(0x306230), rgb(0x8BAC0F)],
+            },
+            _ => return None,
+        };
+
+        Some(palette)
+    }
+
+    /// Builds a palette from two or four colours in pixel value order, the
+    /// background first, as the CHIP-8 database lists them.
+    pub fn from_colors(colors: &[Color]) -> Option<Palette> {
+        match *colors {
+            [background, foreground] => Some(Palette::two_color(foreground, background)),
+            [background, first, second, both] => Some(Palette {
+                colors: [background, first, second, both],
+            }),
+            _ => None,
+        }
+    }
+
+    /// The colour of every pixel value: the four palette colours followed by
+    /// the `FADE_LEVELS` shades of the foreground fading into the
+    /// background, brightest first.
+    pub fn pixel_colors(&self) -> Vec<Color> {
+        let [background, foreground] = [self.colors[0], self.colors[1]].map(to_rgb);
+        let shades = (0..FADE_LEVELS).map(|shade| {
+            let weight = (FADE_LEVELS - shade) as u32;
+            let [r, g, b] = [0, 1, 2].map(|i| {
+                let mixed = foreground[i] as u32 * weight
+                    + background[i] as u32 * (FADE_LEVELS as u32 + 1 - weight);
+                (mixed / (FADE_LEVELS as u32 + 1)) as u8
+            });
+            Color::Rgb { r, g, b }
+        });
+
+        self.colors.into_iter().chain(shades).collect()
+    }
+
+    fn is_two_color(&self) -> bool {
+        self.colors[2] == self.colors[1] && self.colors[3] == self.colors[1]
+    }
+}
+
+fn rgb(value: u32) -> Color {
+    Color::Rgb {
+        r: (value >> 16) as u8,
+        g: (value >> 8) as u8,
+        b: value as u8,
+    }
+}
+
+/// Parses a `#RRGGBB` colour as used by the CHIP-8 database.
+pub fn parse_rgb(text: &str) -> Option<Color> {
+    let hex = text.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+
+    Some(rgb(u32::from_str_radix(hex, 16).ok()?))
+}
+
+pub fn format_color(color: Color) -> String {
+    match color {
+        Color::Rgb { r, g, b } => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        _ => format!("{:?}", color).to_lowercase(),
+    }
+}
+
+/// Lists the colours of `palette`, background first.
+pub fn format_palette(palette: &Palette) -> String {
+    let count = if palette.is_two_color() { 2 } else { 4 };
+    let colors: Vec<String> = palette.colors[..count]
+        .iter()
+        .map(|&color| format_color(color))
+        .collect();
+
+    colors.join(",")
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::two_color(Color::White, Color::Black)
+    }
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    /// Accepts a preset name or two or four comma-separated `#RRGGBB`
+    /// colours, background first.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(palette) = Palette::named(s) {
+            return Ok(palette);
+        }
+
+        let colors: Option<Vec<Color>> = s.split(',').map(|c| parse_rgb(c.trim())).collect();
+        colors
+            .and_then(|colors| Palette::from_colors(&colors))
+            .ok_or_else(|| {
+                format!(
+                    "unknown palette '{}' (expected one of: {}, or two or four \
+                     #rrggbb colours separated by commas, background first)",
+                    s,
+                    PRESETS.join(", ")
+                )
+            })
+    }
+}
+
+/// The colours a terminal can show.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorDepth {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+}
+
+// The usual RGB values of the 16 ANSI colours, as xterm shows them.
+const ANSI16: [(Color, [u8; 3]); 16] = [
+    (Color::Black, [0x00, 0x00, 0x00]),
+    (Color::DarkRed, [0xCD, 0x00, 0x00]),
+    (Color::DarkGreen, [0x00, 0xCD, 0x00]),
+    (Color::DarkYellow, [0xCD, 0xCD, 0x00]),
+    (Color::DarkBlue, [0x00, 0x00, 0xEE]),
+    (Color::DarkMagenta, [0xCD, 0x00, 0xCD]),
+    (Color::DarkCyan, [0x00, 0xCD, 0xCD]),
+    (Color::Grey, [0xE5, 0xE5, 0xE5]),
+    (Color::DarkGrey, [0x7F, 0x7F, 0x7F]),
+    (Color::Red, [0xFF, 0x00, 0x00]),
+    (Color::Green, [0x00, 0xFF, 0x00]),
+    (Color::Yellow, [0xFF, 0xFF, 0x00]),
+    (Color::Blue, [0x5C, 0x5C, 0xFF]),
+    (Color::Magenta, [0xFF, 0x00, 0xFF]),
+    (Color::Cyan, [0x00, 0xFF, 0xFF]),
+    (Color::White, [0xFF, 0xFF, 0xFF]),
+];
+
+// Channel values of the 6x6x6 colour cube in the 256-colour palette, which
+// starts at index 16. The 24 greys from index 232 on are 8, 18, ..., 238.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl ColorDepth {
+    /// Guesses the depth from `COLORTERM` and `TERM`, the way most programs
+    /// do since terminals cannot be asked reliably.
+    pub fn detect() -> ColorDepth {
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        let term = env::var("TERM").unwrap_or_default();
+
+        if colorterm == "truecolor" || colorterm == "24bit" || term.ends_with("-direct") {
+            ColorDepth::TrueColor
+        } else if term.contains("256color") {
+            ColorDepth::Ansi256
+        } else {
+            ColorDepth::Ansi16
+        }
+    }
+
+    /// Replaces an RGB colour with the closest one available. Named colours
+    /// are left alone.
+    pub fn reduce(self, color: Color) -> Color {
+        let Color::Rgb { r, g, b } = color else {
+            return color;
+        };
+        let rgb = [r, g, b];
+
+        match self {
+            ColorDepth::TrueColor => color,
+            ColorDepth::Ansi256 => {
+                let cube = rgb.map(|channel| {
+                    (0..CUBE_LEVELS.len())
+                        .min_by_key(|&i| CUBE_LEVELS[i].abs_diff(channel))
+                        .unwrap()
+                });
+                let cube_rgb = cube.map(|i| CUBE_LEVELS[i]);
+
+                let average = rgb.iter().map(|&c| c as u32).sum::<u32>() / 3;
+                let grey = (average.saturating_sub(3) / 10).min(23) as u8;
+                let grey_value = 8 + 10 * grey;
+
+                if distance(rgb, cube_rgb) <= distance(rgb, [grey_value; 3]) {
+                    Color::AnsiValue(16 + 36 * cube[0] as u8 + 6 * cube[1] as u8 + cube[2] as u8)
+                } else {
+                    Color::AnsiValue(232 + grey)
+                }
+            }
+            ColorDepth::Ansi16 => {
+                ANSI16
+                    .iter()
+                    .min_by_key(|(_, value)| distance(rgb, *value))
+                    .unwrap()
+                    .0
+            }
+        }
+    }
+}
+
+/// The RGB value of `color`, taking the usual xterm values for the 16 and
+/// 256 colour palettes.
+pub fn to_rgb(color: Color) -> [u8; 3] {
+    match color {
+        Color::Rgb { r, g, b } => [r, g, b],
+        Color::AnsiValue(value @ 0..=15) => ANSI16[value as usize].1,
+        Color::AnsiValue(value @ 16..=231) => {
+            let cube = value - 16;
+            [cube / 36, cube / 6 % 6, cube % 6].map(|i| CUBE_LEVELS[i as usize])
+        }
+        Color::AnsiValue(value) => [8 + 10 * (value - 232); 3],
+        _ => ANSI16
+            .iter()
+            .find(|(named, _)| *named == color)
+            .map_or([0; 3], |(_, rgb)| *rgb),
+    }
+}
+
+fn distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter()
+        .zip(b)
+        .map(|(&x, y)| (x.abs_diff(y) as u32).pow(2))
+        .sum()
+}
@@ -0,0 +1,17 @@
+use clap::ValueEnum;
+
+/// What to do when the interpreter meets an opcode it cannot decode, or an
+/// instruction it cannot execute such as a call with the stack full.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OpcodePolicy {
+    /// Stop emulation and report the opcode, recent instructions and call
+    /// stack
+    #[default]
+    Halt,
+    /// Pause emulation and show the report, resuming skips the opcode
+    Break,
+    /// Skip the opcode silently
+    Skip,
+    /// Skip the opcode and report it on stderr
+    Log,
+}
@@ -1,55 +1,222 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
 use crate::{
+    analysis::{family, Family},
+    audio::{Pattern, DEFAULT_PITCH},
+    disassembler::disassemble,
     display::Display,
+    font::BIG_GLYPH_SIZE,
     keypad::{Key, Keypad},
+    machine::Machine,
     memory::Memory,
-    opcode::Opcode,
+    opcode::{Instruction, Opcode},
     program_counter::ProgramCounter,
-    stack::Stack,
+    quirks::Quirks,
+    stack::{Stack, DEFAULT_STACK_DEPTH},
     timer::Timer,
+    timing::{vip_cycles, Timing, VIP_CYCLES_PER_FRAME, VIP_INTERRUPT_CYCLES},
 };
 
-pub struct Chip8<'a> {
+const HISTORY_LENGTH: usize = 8;
+
+pub const DEFAULT_INSTRUCTIONS_PER_FRAME: u32 = 10;
+
+/// Why an instruction could not be executed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FaultKind {
+    /// The opcode does not decode to an instruction.
+    UndefinedOpcode,
+    /// A call with every level of the stack in use.
+    StackOverflow,
+    /// A return with nothing to return to.
+    StackUnderflow,
+}
+
+/// An instruction that could not be executed, together with the
+/// instructions executed before it and the calls it is nested in.
+#[derive(Debug)]
+pub struct Fault {
+    pub kind: FaultKind,
+    pub address: u16,
+    pub opcode: u16,
+    /// `(address, opcode)` of the most recent instructions, oldest first,
+    /// ending with the faulty one.
+    pub history: Vec<(u16, u16)>,
+    /// Return addresses on the stack, the innermost call first.
+    pub call_stack: Vec<u16>,
+    /// Return addresses the stack has room for.
+    pub stack_limit: usize,
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            FaultKind::UndefinedOpcode => {
+                write!(
+                    f,
+                    "undefined opcode {:04X} at {:#05X}",
+                    self.opcode, self.address
+                )?;
+
+                match family(self.opcode) {
+                    Family::MachineCode => write!(f, " (machine code call)")?,
+                    Family::Schip | Family::Xochip => {
+                        write!(f, " ({} instruction)", family(self.opcode))?
+                    }
+                    _ => {}
+                }
+            }
+            FaultKind::StackOverflow => write!(
+                f,
+                "stack overflow at {:#05X}, all {} levels are in use",
+                self.address, self.stack_limit
+            )?,
+            FaultKind::StackUnderflow => write!(
+                f,
+                "stack underflow at {:#05X}, there is no call to return from",
+                self.address
+            )?,
+        }
+
+        writeln!(f)?;
+        write!(f, "recent instructions:")?;
+        for (address, opcode) in &self.history {
+            write!(
+                f,
+                "\n  {:04X}: {:04X}  {}",
+                address,
+                opcode,
+                disassemble(*opcode)
+            )?;
+        }
+
+        if !self.call_stack.is_empty() {
+            write!(f, "\ncall stack:")?;
+            for address in &self.call_stack {
+                write!(f, "\n  returns to {:#05X}", address)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for Fault {}
+
+pub struct Chip8 {
     program_counter: ProgramCounter,
     memory: Memory,
+    initial_memory: Memory,
     registers: [u8; 16],
     index_register: u16,
     stack: Stack,
-    display: &'a mut Display,
+    display: Display,
     delay_timer: Timer,
     sound_timer: Timer,
+    audio_pattern: Option<[u8; 16]>,
+    pitch: u8,
     key_pad: Keypad,
+    quirks: Quirks,
+    rng: StdRng,
+    seed: Option<u64>,
+    instructions_per_frame: u32,
+    timing: Timing,
+    // Instructions run so far in this frame, for IPF timing.
+    executed: u32,
+    // Machine cycles left in this frame, for VIP timing. An instruction that
+    // runs past the end of a frame takes its cycles from the next one.
+    cycles: i32,
+    // Whether the display interrupt just ended a wait for it, so a sprite
+    // may be drawn.
+    interrupted: bool,
+    instructions: u64,
+    redraw: bool,
+    buzzing: bool,
+    waiting_for_vblank: bool,
+    history: VecDeque<(u16, u16)>,
 }
 
-impl<'a> Chip8<'a> {
-    pub fn new(display: &mut Display, memory: Memory) -> Chip8 {
+impl Chip8 {
+    pub fn new(memory: Memory, quirks: Quirks) -> Chip8 {
         Chip8 {
-            program_counter: ProgramCounter::new(),
+            program_counter: ProgramCounter::new(memory.map().program_address),
+            initial_memory: memory.clone(),
             memory,
             registers: [0; 16],
             index_register: 0,
-            stack: Stack::new(),
-            display,
+            stack: Stack::new(DEFAULT_STACK_DEPTH, false),
+            display: Display::new(64, 32),
             delay_timer: Timer::new(),
             sound_timer: Timer::new(),
+            audio_pattern: None,
+            pitch: DEFAULT_PITCH,
             key_pad: Keypad::new(),
+            quirks,
+            rng: StdRng::from_entropy(),
+            seed: None,
+            instructions_per_frame: DEFAULT_INSTRUCTIONS_PER_FRAME,
+            timing: Timing::Ipf,
+            executed: 0,
+            cycles: VIP_CYCLES_PER_FRAME - VIP_INTERRUPT_CYCLES,
+            interrupted: false,
+            instructions: 0,
+            redraw: false,
+            buzzing: false,
+            waiting_for_vblank: false,
+            history: VecDeque::with_capacity(HISTORY_LENGTH),
         }
     }
 
+    /// Makes CXKK deterministic, so runs can be reproduced.
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+        self.seed = Some(seed);
+    }
+
+    /// Gives the stack room for `depth` return addresses, kept in memory
+    /// like on the VIP when `in_memory` is set.
+    pub fn set_stack(&mut self, depth: u16, in_memory: bool) {
+        self.stack = Stack::new(depth, in_memory);
+    }
+
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.timing = timing;
+    }
+
     fn op_00e0(&mut self) {
         self.display.clear();
+        self.redraw = true;
+        self.store_display();
     }
 
-    fn op_00ee(&mut self) {
-        self.program_counter.set(self.stack.pop());
+    fn op_00ee(&mut self) -> Result<(), FaultKind> {
+        let address = self
+            .stack
+            .pop(&self.memory)
+            .ok_or(FaultKind::StackUnderflow)?;
+        self.program_counter.set(address);
+
+        Ok(())
     }
 
     fn op_1nnn(&mut self, opcode: &Opcode) {
         self.program_counter.set(opcode.nnn());
     }
 
-    fn op_2nnn(&mut self, opcode: &Opcode) {
-        self.stack.push(self.program_counter.get());
+    fn op_2nnn(&mut self, opcode: &Opcode) -> Result<(), FaultKind> {
+        if !self
+            .stack
+            .push(&mut self.memory, self.program_counter.get())
+        {
+            return Err(FaultKind::StackOverflow);
+        }
         self.program_counter.set(opcode.nnn());
+
+        Ok(())
     }
 
     fn op_3xkk(&mut self, opcode: &Opcode) {
@@ -83,18 +250,24 @@ impl<'a> Chip8<'a> {
     }
 
     fn op_8xy1(&mut self, opcode: &Opcode) {
-        self.registers[0xF] = 0;
         self.registers[opcode.x] |= self.registers[opcode.y];
+        if self.quirks.vf_reset {
+            self.registers[0xF] = 0;
+        }
     }
 
     fn op_8xy2(&mut self, opcode: &Opcode) {
-        self.registers[0xF] = 0;
         self.registers[opcode.x] &= self.registers[opcode.y];
+        if self.quirks.vf_reset {
+            self.registers[0xF] = 0;
+        }
     }
 
     fn op_8xy3(&mut self, opcode: &Opcode) {
-        self.registers[0xF] = 0;
         self.registers[opcode.x] ^= self.registers[opcode.y];
+        if self.quirks.vf_reset {
+            self.registers[0xF] = 0;
+        }
     }
 
     fn op_8xy4(&mut self, opcode: &Opcode) {
@@ -110,7 +283,9 @@ impl<'a> Chip8<'a> {
     }
 
     fn op_8xy6(&mut self, opcode: &Opcode) {
-        self.registers[opcode.x] = self.registers[opcode.y];
+        if !self.quirks.shifting {
+            self.registers[opcode.x] = self.registers[opcode.y];
+        }
 
         let lsb = self.registers[opcode.x] & 0x1;
         self.registers[opcode.x] >>= 1;
@@ -124,7 +299,9 @@ impl<'a> Chip8<'a> {
     }
 
     fn op_8xye(&mut self, opcode: &Opcode) {
-        self.registers[opcode.x] = self.registers[opcode.y];
+        if !self.quirks.shifting {
+            self.registers[opcode.x] = self.registers[opcode.y];
+        }
 
         let msb = (self.registers[opcode.x] & 0x80) >> 7;
         self.registers[opcode.x] <<= 1;
@@ -142,44 +319,54 @@ impl<'a> Chip8<'a> {
     }
 
     fn op_bnnn(&mut self, opcode: &Opcode) {
-        self.program_counter
-            .set(opcode.nnn() + self.registers[0] as u16);
+        let offset = if self.quirks.jumping {
+            self.registers[opcode.x]
+        } else {
+            self.registers[0]
+        };
+
+        self.program_counter.set(opcode.nnn() + offset as u16);
     }
 
     fn op_cxkk(&mut self, opcode: &Opcode) {
-        self.registers[opcode.x] = rand::random::<u8>() & opcode.kk();
+        self.registers[opcode.x] = self.rng.gen::<u8>() & opcode.kk();
     }
 
     fn op_dxyn(&mut self, opcode: &Opcode) {
-        let x = self.registers[opcode.x] as u16 % self.display.width();
-        let y = self.registers[opcode.y] as u16 % self.display.height();
+        let width = self.display.width();
+        let height = self.display.height();
+        let x = self.registers[opcode.x] as u16 % width;
+        let y = self.registers[opcode.y] as u16 % height;
         self.registers[0xF] = 0;
 
-        for row in 0..opcode.n {
-            if y + row as u16 >= self.display.height() {
+        for row in 0..opcode.n as u16 {
+            if self.quirks.clipping && y + row >= height {
                 break;
             }
 
-            let sprite_byte = self.memory.get_as_u8(self.index_register + row as u16);
+            let sprite_byte = self.memory.get_as_u8(self.index_register.wrapping_add(row));
 
             for col in 0..8 {
-                let pixel = (sprite_byte & (0x80 >> col)) >> 7 - col;
+                let pixel = (sprite_byte & (0x80 >> col)) >> (7 - col);
 
-                if x + col as u16 >= self.display.width() {
+                if self.quirks.clipping && x + col >= width {
                     break;
                 }
 
-                if pixel == 1 {
-                    if !self
+                if pixel == 1
+                    && !self
                         .display
-                        .flip_pixel((x + col) as usize, (y + row as u16) as usize)
-                    {
-                        self.registers[0xF] = 1;
-                    }
+                        .flip_pixel(((x + col) % width) as usize, ((y + row) % height) as usize)
+                {
+                    self.registers[0xF] = 1;
                 }
             }
         }
-        self.display.display();
+
+        self.redraw = true;
+        self.store_display();
+        // With VIP timing the wait comes before drawing instead.
+        self.waiting_for_vblank = self.quirks.display_wait && self.timing == Timing::Ipf;
     }
 
     fn op_ex9e(&mut self, opcode: &Opcode) {
@@ -199,11 +386,9 @@ impl<'a> Chip8<'a> {
     }
 
     fn op_fx0a(&mut self, opcode: &Opcode) {
-        let res = self.key_pad.get_any_key_up();
-        if res.is_some() {
-            self.registers[opcode.x] = res.unwrap();
-        } else {
-            self.program_counter.decrement();
+        match self.key_pad.get_any_key_up() {
+            Some(key) => self.registers[opcode.x] = key,
+            None => self.program_counter.decrement(),
         }
     }
 
@@ -215,19 +400,48 @@ impl<'a> Chip8<'a> {
         self.sound_timer.set(self.registers[opcode.x]);
     }
 
+    fn op_f002(&mut self) {
+        let mut pattern = [0; 16];
+        for (i, byte) in pattern.iter_mut().enumerate() {
+            *byte = self
+                .memory
+                .get_as_u8(self.index_register.wrapping_add(i as u16));
+        }
+        self.audio_pattern = Some(pattern);
+    }
+
+    fn op_fx3a(&mut self, opcode: &Opcode) {
+        self.pitch = self.registers[opcode.x];
+    }
+
     fn op_fx1e(&mut self, opcode: &Opcode) {
-        self.index_register += self.registers[opcode.x] as u16;
+        self.index_register = self
+            .index_register
+            .wrapping_add(self.registers[opcode.x] as u16);
     }
 
     fn op_fx29(&mut self, opcode: &Opcode) {
-        self.index_register = (self.registers[opcode.x] * 5 + 0x50) as u16;
+        self.index_register = self
+            .memory
+            .map()
+            .font_address
+            .wrapping_add(self.registers[opcode.x] as u16 * 5);
+    }
+
+    fn op_fx30(&mut self, opcode: &Opcode) {
+        self.index_register = self
+            .memory
+            .map()
+            .big_font_address()
+            .wrapping_add(self.registers[opcode.x] as u16 * BIG_GLYPH_SIZE as u16);
     }
 
     fn op_fx33(&mut self, opcode: &Opcode) {
         let mut value = self.registers[opcode.x];
 
         for i in (0..=2).rev() {
-            self.memory.set(self.index_register + i, value % 10);
+            self.memory
+                .set(self.index_register.wrapping_add(i), value % 10);
             if i > 0 {
                 value /= 10;
             }
@@ -236,180 +450,323 @@ impl<'a> Chip8<'a> {
 
     fn op_fx55(&mut self, opcode: &Opcode) {
         for i in 0..=opcode.x {
-            self.memory
-                .set(self.index_register as u16, self.registers[i]);
-            self.index_register += 1;
+            self.memory.set(
+                self.index_register.wrapping_add(i as u16),
+                self.registers[i],
+            );
+        }
+
+        if self.quirks.memory_increment {
+            self.index_register = self.index_register.wrapping_add(opcode.x as u16 + 1);
         }
     }
 
     fn op_fx65(&mut self, opcode: &Opcode) {
         for i in 0..=opcode.x {
-            self.registers[i] = self.memory.get_as_u8(self.index_register as u16);
-            self.index_register += 1;
+            self.registers[i] = self
+                .memory
+                .get_as_u8(self.index_register.wrapping_add(i as u16));
         }
-    }
 
-    pub fn handle_key_up(&mut self, key: Key) {
-        self.key_pad.key_up(key);
+        if self.quirks.memory_increment {
+            self.index_register = self.index_register.wrapping_add(opcode.x as u16 + 1);
+        }
     }
 
-    pub fn handle_key_down(&mut self, key: Key) {
-        self.key_pad.key_down(key);
+    fn execute(&mut self, opcode: Opcode) -> Result<(), Fault> {
+        let Some(instruction) = opcode.decode() else {
+            return Err(self.fault(FaultKind::UndefinedOpcode));
+        };
+
+        match instruction {
+            Instruction::Cls => self.op_00e0(),
+            Instruction::Ret => self.op_00ee().map_err(|kind| self.fault(kind))?,
+            Instruction::Jp => self.op_1nnn(&opcode),
+            Instruction::Call => self.op_2nnn(&opcode).map_err(|kind| self.fault(kind))?,
+            Instruction::SeByte => self.op_3xkk(&opcode),
+            Instruction::SneByte => self.op_4xkk(&opcode),
+            Instruction::SeReg => self.op_5xy0(&opcode),
+            Instruction::LdByte => self.op_6xkk(&opcode),
+            Instruction::AddByte => self.op_7xkk(&opcode),
+            Instruction::LdReg => self.op_8xy0(&opcode),
+            Instruction::Or => self.op_8xy1(&opcode),
+            Instruction::And => self.op_8xy2(&opcode),
+            Instruction::Xor => self.op_8xy3(&opcode),
+            Instruction::AddReg => self.op_8xy4(&opcode),
+            Instruction::Sub => self.op_8xy5(&opcode),
+            Instruction::Shr => self.op_8xy6(&opcode),
+            Instruction::Subn => self.op_8xy7(&opcode),
+            Instruction::Shl => self.op_8xye(&opcode),
+            Instruction::SneReg => self.op_9xy0(&opcode),
+            Instruction::LdI => self.op_annn(&opcode),
+            Instruction::JpV0 => self.op_bnnn(&opcode),
+            Instruction::Rnd => self.op_cxkk(&opcode),
+            Instruction::Drw => self.op_dxyn(&opcode),
+            Instruction::Skp => self.op_ex9e(&opcode),
+            Instruction::Sknp => self.op_exa1(&opcode),
+            Instruction::LdVxDt => self.op_fx07(&opcode),
+            Instruction::LdVxK => self.op_fx0a(&opcode),
+            Instruction::LdDtVx => self.op_fx15(&opcode),
+            Instruction::LdStVx => self.op_fx18(&opcode),
+            Instruction::AddI => self.op_fx1e(&opcode),
+            Instruction::LdF => self.op_fx29(&opcode),
+            Instruction::LdHf => self.op_fx30(&opcode),
+            Instruction::LdB => self.op_fx33(&opcode),
+            Instruction::Audio => self.op_f002(),
+            Instruction::Pitch => self.op_fx3a(&opcode),
+            Instruction::Store => self.op_fx55(&opcode),
+            Instruction::Load => self.op_fx65(&opcode),
+        }
+
+        Ok(())
     }
 
-    pub fn emulate_cycle(&mut self) {
-        let opcode = self.fetch();
+    // Copies the display into memory, when it is kept there.
+    fn store_display(&mut self) {
+        if let Some(region) = self.memory.map().display_region() {
+            let bytes = self.display.to_bytes();
+            self.memory.load(region.start, &bytes).unwrap();
+        }
+    }
 
-        match opcode {
-            Opcode {
-                category: 0x0,
-                x: 0x0,
-                y: 0xe,
-                n: 0x0,
-            } => self.op_00e0(),
-            Opcode {
-                category: 0x0,
-                x: 0x0,
-                y: 0xe,
-                n: 0xe,
-            } => self.op_00ee(),
-            Opcode { category: 0x1, .. } => self.op_1nnn(&opcode),
-            Opcode { category: 0x2, .. } => self.op_2nnn(&opcode),
-            Opcode { category: 0x3, .. } => self.op_3xkk(&opcode),
-            Opcode { category: 0x4, .. } => self.op_4xkk(&opcode),
-            Opcode { category: 0x5, .. } => self.op_5xy0(&opcode),
-            Opcode { category: 0x6, .. } => self.op_6xkk(&opcode),
-            Opcode { category: 0x7, .. } => self.op_7xkk(&opcode),
-            Opcode {
-                category: 0x8,
-                n: 0x0,
-                ..
-            } => self.op_8xy0(&opcode),
-            Opcode {
-                category: 0x8,
-                n: 0x1,
-                ..
-            } => self.op_8xy1(&opcode),
-            Opcode {
-                category: 0x8,
-                n: 0x2,
-                ..
-            } => self.op_8xy2(&opcode),
-            Opcode {
-                category: 0x8,
-                n: 0x3,
-                ..
-            } => self.op_8xy3(&opcode),
-            Opcode {
-                category: 0x8,
-                n: 0x4,
-                ..
-            } => self.op_8xy4(&opcode),
-            Opcode {
-                category: 0x8,
-                n: 0x5,
-                ..
-            } => self.op_8xy5(&opcode),
-            Opcode {
-                category: 0x8,
-                n: 0x6,
-                ..
-            } => self.op_8xy6(&opcode),
-            Opcode {
-                category: 0x8,
-                n: 0x7,
-                ..
-            } => self.op_8xy7(&opcode),
-            Opcode {
-                category: 0x8,
-                n: 0xE,
-                ..
-            } => self.op_8xye(&opcode),
-            Opcode { category: 0x9, .. } => self.op_9xy0(&opcode),
-            Opcode { category: 0xA, .. } => self.op_annn(&opcode),
-            Opcode { category: 0xB, .. } => self.op_bnnn(&opcode),
-            Opcode { category: 0xC, .. } => self.op_cxkk(&opcode),
-            Opcode { category: 0xD, .. } => self.op_dxyn(&opcode),
-            Opcode {
-                category: 0xE,
-                y: 0x9,
-                n: 0xE,
-                ..
-            } => self.op_ex9e(&opcode),
-            Opcode {
-                category: 0xE,
-                y: 0xA,
-                n: 0x1,
-                ..
-            } => self.op_exa1(&opcode),
-            Opcode {
-                category: 0xF,
-                y: 0x0,
-                n: 0x7,
-                ..
-            } => self.op_fx07(&opcode),
-            Opcode {
-                category: 0xF,
-                y: 0x0,
-                n: 0xA,
-                ..
-            } => self.op_fx0a(&opcode),
-            Opcode {
-                category: 0xF,
-                y: 0x1,
-                n: 0x5,
-                ..
-            } => self.op_fx15(&opcode),
-            Opcode {
-                category: 0xF,
-                y: 0x1,
-                n: 0x8,
-                ..
-            } => self.op_fx18(&opcode),
-            Opcode {
-                category: 0xF,
-                y: 0x1,
-                n: 0xE,
-                ..
-            } => self.op_fx1e(&opcode),
-            Opcode {
-                category: 0xF,
-                y: 0x2,
-                n: 0x9,
-                ..
-            } => self.op_fx29(&opcode),
-            Opcode {
-                category: 0xF,
-                y: 0x3,
-                n: 0x3,
-                ..
-            } => self.op_fx33(&opcode),
-            Opcode {
-                category: 0xF,
-                y: 0x5,
-                n: 0x5,
-                ..
-            } => self.op_fx55(&opcode),
-            Opcode {
-                category: 0xF,
-                y: 0x6,
-                n: 0x5,
-                ..
-            } => self.op_fx65(&opcode),
-            _ => {
-                println!("Unsupported operation: {}", opcode.category);
+    // Shows what instructions wrote to the display in memory.
+    fn load_display(&mut self) {
+        if self.memory.take_display_written() {
+            if let Some(bytes) = self.memory.display_bytes() {
+                self.redraw |= self.display.set_bytes(bytes);
             }
         }
+    }
 
-        self.delay_timer.tick();
-        self.sound_timer.tick();
+    // Reports the last instruction fetched as faulty.
+    fn fault(&self, kind: FaultKind) -> Fault {
+        let (address, opcode) = *self.history.back().unwrap();
+
+        Fault {
+            kind,
+            address,
+            opcode,
+            history: self.history.iter().copied().collect(),
+            call_stack: self.call_stack(),
+            stack_limit: self.stack.limit(),
+        }
     }
 
     fn fetch(&mut self) -> Opcode {
         let program_counter = self.program_counter.get();
         let opcode = self.memory.get_as_u16(program_counter);
         self.program_counter.increment();
+        self.instructions += 1;
+
+        if self.history.len() == HISTORY_LENGTH {
+            self.history.pop_front();
+        }
+        self.history.push_back((program_counter, opcode));
 
         opcode.into()
     }
 }
+
+/// Whether `execute` carries out `word` as the instruction it stands for on
+/// its platform. DXY0, a 16x16 sprite on SUPER-CHIP, runs but draws nothing
+/// here.
+pub fn emulated(word: u16) -> bool {
+    let opcode: Opcode = word.into();
+
+    match opcode.decode() {
+        Some(Instruction::Drw) => opcode.n != 0x0,
+        Some(_) => true,
+        None => false,
+    }
+}
+
+impl Machine for Chip8 {
+    fn display(&self) -> &Display {
+        &self.display
+    }
+
+    /// Returns whether the display changed since the last call.
+    fn take_redraw(&mut self) -> bool {
+        std::mem::take(&mut self.redraw)
+    }
+
+    /// Whether this frame has had all the instructions it gets: as many as
+    /// the IPF, or with VIP timing as many as fit in its cycles or until
+    /// drawing waits for the display interrupt.
+    fn frame_finished(&self) -> bool {
+        match self.timing {
+            Timing::Ipf => self.executed >= self.instructions_per_frame,
+            Timing::Vip => self.cycles <= 0 || self.waiting_for_vblank,
+        }
+    }
+
+    /// Executes one instruction. An opcode that does not decode is skipped
+    /// and reported; what happens next is up to the caller.
+    fn emulate_cycle(&mut self) -> Result<(), Fault> {
+        self.executed += 1;
+        if self.waiting_for_vblank {
+            return Ok(());
+        }
+
+        // The VIP interpreter draws a sprite only once the display interrupt
+        // has happened, which takes the rest of the frame.
+        if self.timing == Timing::Vip
+            && !self.interrupted
+            && self.memory.get_as_u8(self.program_counter.get()) >> 4 == 0xD
+        {
+            self.waiting_for_vblank = true;
+            return Ok(());
+        }
+        self.interrupted = false;
+
+        let opcode = self.fetch();
+        let vx = self.registers[opcode.x];
+        let next = self.program_counter.get();
+        let result = self.execute(opcode);
+        self.load_display();
+
+        if self.timing == Timing::Vip {
+            let skipped = self.program_counter.get() != next;
+            self.cycles -= vip_cycles(&opcode, vx, skipped) as i32;
+        }
+
+        result
+    }
+
+    /// With VIP timing, the share of the interpreter's cycles this frame
+    /// has used.
+    fn frame_position(&self) -> Option<f64> {
+        let available = (VIP_CYCLES_PER_FRAME - VIP_INTERRUPT_CYCLES) as f64;
+        match self.timing {
+            Timing::Ipf => None,
+            Timing::Vip => Some(1.0 - self.cycles.max(0) as f64 / available),
+        }
+    }
+
+    /// Marks the end of a 60 Hz frame: the timers count down and an
+    /// interpreter waiting on the display interrupt may continue.
+    fn end_frame(&mut self) {
+        self.buzzing = self.sound_timer.get() > 0;
+        self.delay_timer.tick();
+        self.sound_timer.tick();
+        self.interrupted = self.waiting_for_vblank;
+        self.waiting_for_vblank = false;
+        self.executed = 0;
+        // Cycles left over are lost waiting, an overrun is carried over.
+        self.cycles = self.cycles.min(0) + VIP_CYCLES_PER_FRAME - VIP_INTERRUPT_CYCLES;
+    }
+
+    /// Instructions executed since the machine was created.
+    fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    /// Whether the buzzer sounded in the frame `end_frame` last ended, which
+    /// a sound timer set during it and run out at its end still counts.
+    fn buzzing(&self) -> bool {
+        self.buzzing
+    }
+
+    /// Whether the sound timer is running, i.e. the buzzer sounds.
+    fn sound_active(&self) -> bool {
+        self.sound_timer.get() > 0
+    }
+
+    /// The XO-CHIP audio pattern the buzzer plays, once F002 loaded one.
+    fn audio_pattern(&self) -> Option<Pattern> {
+        self.audio_pattern.map(|bits| Pattern {
+            bits,
+            pitch: self.pitch,
+        })
+    }
+
+    fn handle_key_down(&mut self, key: Key) {
+        self.key_pad.key_down(key);
+    }
+
+    fn handle_key_up(&mut self, key: Key) {
+        self.key_pad.key_up(key);
+    }
+
+    /// The keypad keys currently held down, in ascending order.
+    fn held_keys(&self) -> Vec<u8> {
+        (0..16)
+            .filter(|&key| self.key_pad.is_key_down(key))
+            .collect()
+    }
+
+    /// Puts the machine back in the state it was created in: memory as it
+    /// was loaded, everything else cleared. Speed, quirks and the seed are
+    /// kept.
+    fn reset(&mut self) {
+        self.program_counter = ProgramCounter::new(self.initial_memory.map().program_address);
+        self.memory = self.initial_memory.clone();
+        self.registers = [0; 16];
+        self.index_register = 0;
+        self.stack.clear();
+        self.display.clear();
+        self.delay_timer = Timer::new();
+        self.sound_timer = Timer::new();
+        self.audio_pattern = None;
+        self.pitch = DEFAULT_PITCH;
+        self.key_pad = Keypad::new();
+        self.redraw = true;
+        self.buzzing = false;
+        self.waiting_for_vblank = false;
+        self.executed = 0;
+        self.cycles = VIP_CYCLES_PER_FRAME - VIP_INTERRUPT_CYCLES;
+        self.interrupted = false;
+        self.history.clear();
+
+        if let Some(seed) = self.seed {
+            self.seed(seed);
+        }
+    }
+
+    fn load_program(&mut self, program: &[u8]) -> Result<(), String> {
+        self.initial_memory = self.initial_memory.with_other_program(program)?;
+        self.reset();
+
+        Ok(())
+    }
+
+    /// The address of the next instruction.
+    fn program_counter(&self) -> u16 {
+        self.program_counter.get()
+    }
+
+    /// Return addresses on the stack, the innermost call first.
+    fn call_stack(&self) -> Vec<u16> {
+        self.stack.return_addresses(&self.memory)
+    }
+
+    fn instructions_per_frame(&self) -> Option<u32> {
+        (self.timing == Timing::Ipf).then_some(self.instructions_per_frame)
+    }
+
+    fn set_instructions_per_frame(&mut self, instructions_per_frame: u32) {
+        self.instructions_per_frame = instructions_per_frame.max(1);
+    }
+
+    fn pacing(&self) -> String {
+        match self.timing {
+            Timing::Ipf => format!("IPF {}", self.instructions_per_frame),
+            Timing::Vip => "VIP timing".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emulated_opcodes() {
+        for word in [0x00E0, 0xF002, 0xF13A, 0xF230, 0xD125] {
+            assert!(emulated(word), "{:04X}", word);
+        }
+        for word in [0x0123, 0x5121, 0x800F, 0xD120, 0xE19F, 0xF0FF] {
+            assert!(!emulated(word), "{:04X}", word);
+        }
+    }
+}
@@ -3,16 +3,17 @@ pub struct ProgramCounter {
 }
 
 impl ProgramCounter {
-    pub fn new() -> ProgramCounter {
-        ProgramCounter { counter: 0x200 }
+    /// A program counter pointing at `start`, where the program is loaded.
+    pub fn new(start: u16) -> ProgramCounter {
+        ProgramCounter { counter: start }
     }
 
     pub fn increment(&mut self) {
-        self.counter += 2
+        self.counter = self.counter.wrapping_add(2)
     }
 
     pub fn decrement(&mut self) {
-        self.counter -= 2
+        self.counter = self.counter.wrapping_sub(2)
     }
 
     pub fn set(&mut self, new_counter_value: u16) {
@@ -20,6 +21,6 @@ impl ProgramCounter {
     }
 
     pub fn get(&self) -> u16 {
-        return self.counter;
+        self.counter
     }
 }
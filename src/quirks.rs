@@ -0,0 +1,139 @@
+use std::fmt;
+
+use clap::ValueEnum;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Platform {
+    /// The original COSMAC VIP interpreter
+    #[default]
+    Chip8,
+    /// SUPER-CHIP 1.1 on the HP 48
+    Schip,
+    /// Octo's XO-CHIP extension
+    Xochip,
+}
+
+impl Platform {
+    /// Return addresses the platform's interpreter has room for on its
+    /// stack.
+    pub fn stack_depth(self) -> u16 {
+        match self {
+            Platform::Chip8 => 12,
+            Platform::Schip | Platform::Xochip => 16,
+        }
+    }
+}
+
+/// Behaviour that differs between CHIP-8 interpreters. Each flag is named
+/// after the quirk as described by Timendus' CHIP-8 test suite.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8XY1, 8XY2 and 8XY3 reset VF to zero.
+    pub vf_reset: bool,
+    /// 8XY6 and 8XYE shift VX in place instead of copying VY first.
+    pub shifting: bool,
+    /// FX55 and FX65 leave I pointing past the last register transferred.
+    pub memory_increment: bool,
+    /// BNNN is decoded as BXNN and jumps to XNN + VX.
+    pub jumping: bool,
+    /// Sprites are clipped at the screen edges instead of wrapping.
+    pub clipping: bool,
+    /// DXYN waits for the next frame before the interpreter continues.
+    pub display_wait: bool,
+}
+
+impl Quirks {
+    pub fn for_platform(platform: Platform) -> Quirks {
+        match platform {
+            Platform::Chip8 => Quirks {
+                vf_reset: true,
+                shifting: false,
+                memory_increment: true,
+                jumping: false,
+                clipping: true,
+                display_wait: true,
+            },
+            Platform::Schip => Quirks {
+                vf_reset: false,
+                shifting: true,
+                memory_increment: false,
+                jumping: true,
+                clipping: true,
+                display_wait: false,
+            },
+            Platform::Xochip => Quirks {
+                vf_reset: false,
+                shifting: false,
+                memory_increment: true,
+                jumping: false,
+                clipping: false,
+                display_wait: false,
+            },
+        }
+    }
+}
+
+impl fmt::Display for Quirks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let enabled: Vec<&str> = [
+            (self.vf_reset, "vf_reset"),
+            (self.shifting, "shifting"),
+            (self.memory_increment, "memory_increment"),
+            (self.jumping, "jumping"),
+            (self.clipping, "clipping"),
+            (self.display_wait, "display_wait"),
+        ]
+        .iter()
+        .filter(|(enabled, _)| *enabled)
+        .map(|(_, name)| *name)
+        .collect();
+
+        if enabled.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", enabled.join(", "))
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks::for_platform(Platform::default())
+    }
+}
+
+/// Individual quirks that replace the ones of the selected preset.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QuirkOverrides {
+    pub vf_reset: Option<bool>,
+    pub shifting: Option<bool>,
+    pub memory_increment: Option<bool>,
+    pub jumping: Option<bool>,
+    pub clipping: Option<bool>,
+    pub display_wait: Option<bool>,
+}
+
+impl QuirkOverrides {
+    /// Returns these overrides with every quirk set in `other` replaced.
+    pub fn overridden_by(self, other: QuirkOverrides) -> QuirkOverrides {
+        QuirkOverrides {
+            vf_reset: other.vf_reset.or(self.vf_reset),
+            shifting: other.shifting.or(self.shifting),
+            memory_increment: other.memory_increment.or(self.memory_increment),
+            jumping: other.jumping.or(self.jumping),
+            clipping: other.clipping.or(self.clipping),
+            display_wait: other.display_wait.or(self.display_wait),
+        }
+    }
+
+    pub fn apply(&self, quirks: Quirks) -> Quirks {
+        Quirks {
+            vf_reset: self.vf_reset.unwrap_or(quirks.vf_reset),
+            shifting: self.shifting.unwrap_or(quirks.shifting),
+            memory_increment: self.memory_increment.unwrap_or(quirks.memory_increment),
+            jumping: self.jumping.unwrap_or(quirks.jumping),
+            clipping: self.clipping.unwrap_or(quirks.clipping),
+            display_wait: self.display_wait.unwrap_or(quirks.display_wait),
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use std::borrow::Cow;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use clap::ValueEnum;
+
+use crate::{
+    display::Display,
+    palette::{to_rgb, Palette},
+};
+
+// Browsers show GIF frames shorter than this many hundredths of a second for
+// much longer, so shorter frames are dropped and their time given to the
+// next.
+const MIN_GIF_DELAY: u64 = 2;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum RecordFormat {
+    /// Animated GIF of the display, in the palette's colours.
+    #[default]
+    Gif,
+    /// asciinema v2 recording of what is drawn in the terminal.
+    Cast,
+}
+
+impl RecordFormat {
+    /// The format a file name asks for: `.cast` files are asciinema
+    /// recordings, everything else is a GIF.
+    pub fn for_path(path: &Path) -> RecordFormat {
+        match path.extension() {
+            Some(extension) if extension == "cast" => RecordFormat::Cast,
+            _ => RecordFormat::Gif,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            RecordFormat::Gif => "gif",
+            RecordFormat::Cast => "cast",
+        }
+    }
+}
+
+/// Writes the frames of the display as an animated GIF, every pixel a
+/// `scale` by `scale` square.
+pub struct GifRecorder {
+    encoder: gif::Encoder<BufWriter<File>>,
+    path: PathBuf,
+    scale: u16,
+    // The frame waiting for its length to be known.
+    pending: Option<Display>,
+    // 60 Hz frames recorded, and hundredths of a second written.
+    frames: u64,
+    written: u64,
+}
+
+impl GifRecorder {
+    pub fn create(
+        path: &Path,
+        display: &Display,
+        palette: &Palette,
+        scale: u16,
+    ) -> Result<GifRecorder, String> {
+        let error =
+            |e: &dyn std::fmt::Display| format!("Could not write {}: {}", path.display(), e);
+        let colors: Vec<u8> = palette
+            .pixel_colors()
+            .into_iter()
+            .flat_map(to_rgb)
+            .collect();
+
+        let file = File::create(path).map_err(|e| error(&e))?;
+        let mut encoder = gif::Encoder::new(
+            BufWriter::new(file),
+            display.width() * scale,
+            display.height() * scale,
+            &colors,
+        )
+        .map_err(|e| error(&e))?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(|e| error(&e))?;
+
+        Ok(GifRecorder {
+            encoder,
+            path: path.to_path_buf(),
+            scale,
+            pending: None,
+            frames: 0,
+            written: 0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Records the display as it is at the end of a frame.
+    pub fn frame(&mut self, display: &Display) -> Result<(), String> {
+        if self.pending.as_ref() != Some(display) {
+            let delay = centiseconds(self.frames) - self.written;
+            if delay >= MIN_GIF_DELAY {
+                self.write_pending(delay)?;
+            }
+            self.pending = Some(display.clone());
+        }
+        self.frames += 1;
+
+        Ok(())
+    }
+
+    /// Writes the last frame and the end of the file.
+    pub fn finish(mut self) -> Result<(), String> {
+        let delay = (centiseconds(self.frames) - self.written).max(MIN_GIF_DELAY);
+        self.write_pending(delay)?;
+
+        self.encoder
+            .into_inner()
+            .and_then(|mut out| out.flush())
+            .map_err(|e| format!("Could not write {}: {}", self.path.display(), e))
+    }
+
+    fn write_pending(&mut self, delay: u64) -> Result<(), String> {
+        let Some(display) = &self.pending else {
+            return Ok(());
+        };
+        let (width, height) = (display.width() * self.scale, display.height() * self.scale);
+
+        let mut buffer = Vec::with_capacity(width as usize * height as usize);
+        for y in 0..height {
+            for x in 0..width {
+                buffer.push(display.get_pixel(x / self.scale, y / self.scale));
+            }
+        }
+
+        let frame = gif::Frame {
+            width,
+            height,
+            delay: delay.min(u16::MAX as u64) as u16,
+            buffer: Cow::Owned(buffer),
+            ..gif::Frame::default()
+        };
+        self.encoder
+            .write_frame(&frame)
+            .map_err(|e| format!("Could not write {}: {}", self.path.display(), e))?;
+        self.written += delay;
+
+        Ok(())
+    }
+}
+
+// Hundredths of a second that `frames` 60 Hz frames last.
+fn centiseconds(frames: u64) -> u64 {
+    frames * 100 / 60
+}
+
+/// Writes everything drawn in the terminal as an asciinema v2 recording,
+/// which `asciinema play` replays in a terminal.
+pub struct Cast {
+    out: BufWriter<File>,
+    path: PathBuf,
+    start: Instant,
+    // The first write that failed, reported by `finish`.
+    error: Option<io::Error>,
+}
+
+impl Cast {
+    pub fn create(path: &Path, columns: u16, rows: u16) -> Result<Cast, String> {
+        let error = |e: io::Error| format!("Could not write {}: {}", path.display(), e);
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        let header = serde_json::json!({
+            "version": 2,
+            "width": columns,
+            "height": rows,
+            "timestamp": timestamp,
+            "env": { "TERM": env::var("TERM").unwrap_or_default() },
+        });
+
+        let mut out = BufWriter::new(File::create(path).map_err(error)?);
+        writeln!(out, "{}", header).map_err(error)?;
+
+        Ok(Cast {
+            out,
+            path: path.to_path_buf(),
+            start: Instant::now(),
+            error: None,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Records `bytes` written to the terminal now.
+    pub fn output(&mut self, bytes: &[u8]) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let event = serde_json::json!([
+            self.start.elapsed().as_secs_f64(),
+            "o",
+            String::from_utf8_lossy(bytes),
+        ]);
+        if let Err(e) = writeln!(self.out, "{}", event) {
+            self.error = Some(e);
+        }
+    }
+
+    pub fn finish(mut self) -> Result<(), String> {
+        let result = match self.error.take() {
+            Some(e) => Err(e),
+            None => self.out.flush(),
+        };
+
+        result.map_err(|e| format!("Could not write {}: {}", self.path.display(), e))
+    }
+}
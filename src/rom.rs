@@ -0,0 +1,55 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use sha1::{Digest, Sha1};
+
+pub struct Rom {
+    pub path: PathBuf,
+    pub data: Vec<u8>,
+    pub sha1: String,
+}
+
+impl Rom {
+    pub fn read(path: &Path) -> Result<Rom, String> {
+        let data =
+            fs::read(path).map_err(|e| format!("could not read '{}': {}", path.display(), e))?;
+
+        Ok(Rom {
+            path: path.to_path_buf(),
+            sha1: sha1_hex(&data),
+            data,
+        })
+    }
+
+    pub fn file_name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+}
+
+pub fn sha1_hex(data: &[u8]) -> String {
+    Sha1::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// The CRC-32 used by zip and most ROM catalogues.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0_u32;
+
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
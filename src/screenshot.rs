@@ -0,0 +1,159 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use clap::ValueEnum;
+
+use crate::{
+    display::Display,
+    palette::{to_rgb, Palette},
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ImageFormat {
+    #[default]
+    Png,
+    /// Binary portable pixmap, in the palette's colours.
+    Ppm,
+    /// Binary portable bitmap, black on white whatever the palette.
+    Pbm,
+}
+
+impl ImageFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Ppm => "ppm",
+            ImageFormat::Pbm => "pbm",
+        }
+    }
+}
+
+/// `<ROM name>-<UTC date and time>.<extension>` in `directory`, for
+/// screenshots and recordings made while playing.
+pub fn timestamped_path(directory: &Path, rom: &Path, extension: &str) -> PathBuf {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+
+    directory.join(format!(
+        "{}-{}.{}",
+        stem(rom),
+        format_timestamp(seconds),
+        extension
+    ))
+}
+
+/// `<ROM name>-frame<frame>.<extension>` in `directory`, for screenshots
+/// taken at a given frame, which are the same on every run.
+pub fn frame_path(directory: &Path, rom: &Path, frame: u64, format: ImageFormat) -> PathBuf {
+    directory.join(format!(
+        "{}-frame{}.{}",
+        stem(rom),
+        frame,
+        format.extension()
+    ))
+}
+
+fn stem(rom: &Path) -> String {
+    rom.file_stem()
+        .map_or("screenshot".into(), |stem| stem.to_string_lossy().into())
+}
+
+/// Saves the display to `path`, every pixel a `scale` by `scale` square in
+/// the colour `palette` has for its value.
+pub fn save(
+    display: &Display,
+    palette: &Palette,
+    scale: u16,
+    format: ImageFormat,
+    path: &Path,
+) -> Result<(), String> {
+    let error = |e: &dyn std::fmt::Display| format!("Could not write {}: {}", path.display(), e);
+    let colors: Vec<[u8; 3]> = palette.pixel_colors().into_iter().map(to_rgb).collect();
+    let (width, height) = (
+        display.width() as u32 * scale as u32,
+        display.height() as u32 * scale as u32,
+    );
+    let pixel =
+        |x: u32, y: u32| display.get_pixel((x / scale as u32) as u16, (y / scale as u32) as u16);
+
+    let file = File::create(path).map_err(|e| error(&e))?;
+    let mut out = BufWriter::new(file);
+
+    match format {
+        ImageFormat::Png => {
+            let mut encoder = png::Encoder::new(out, width, height);
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+
+            let mut data = Vec::with_capacity((width * height * 3) as usize);
+            for y in 0..height {
+                for x in 0..width {
+                    data.extend_from_slice(&colors[pixel(x, y) as usize]);
+                }
+            }
+
+            let mut writer = encoder.write_header().map_err(|e| error(&e))?;
+            writer.write_image_data(&data).map_err(|e| error(&e))?;
+            writer.finish().map_err(|e| error(&e))
+        }
+        ImageFormat::Ppm => {
+            write!(out, "P6\n{} {}\n255\n", width, height).map_err(|e| error(&e))?;
+            for y in 0..height {
+                for x in 0..width {
+                    out.write_all(&colors[pixel(x, y) as usize])
+                        .map_err(|e| error(&e))?;
+                }
+            }
+            out.flush().map_err(|e| error(&e))
+        }
+        ImageFormat::Pbm => {
+            write!(out, "P4\n{} {}\n", width, height).map_err(|e| error(&e))?;
+            // Rows are packed eight pixels to a byte, 1 being black.
+            for y in 0..height {
+                let mut row = vec![0_u8; width.div_ceil(8) as usize];
+                for x in 0..width {
+                    if pixel(x, y) != 0 {
+                        row[(x / 8) as usize] |= 0x80 >> (x % 8);
+                    }
+                }
+                out.write_all(&row).map_err(|e| error(&e))?;
+            }
+            out.flush().map_err(|e| error(&e))
+        }
+    }
+}
+
+// Formats seconds since the Unix epoch as `YYYYMMDD-HHMMSS` in UTC.
+fn format_timestamp(seconds: u64) -> String {
+    let (days, time) = (seconds / 86_400, seconds % 86_400);
+
+    // Converts days since 1970-01-01 to a civil date, after Howard Hinnant's
+    // `civil_from_days`.
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
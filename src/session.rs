@@ -0,0 +1,115 @@
+use std::io::{self, stdout};
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Once};
+
+use crossterm::{
+    cursor,
+    event::{KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags},
+    execute, terminal,
+};
+use signal_hook::SigId;
+
+// Whether the terminal is currently set up for the emulator, so restoring it
+// from the panic hook, a signal and `Drop` happens exactly once.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static KEYBOARD_ENHANCED: AtomicBool = AtomicBool::new(false);
+static INSTALL_PANIC_HOOK: Once = Once::new();
+
+/// Owns the terminal while the emulator runs: raw mode, the alternate screen
+/// and a hidden cursor. Everything is put back when the session is dropped,
+/// when the program panics and, through `interrupted`, when it receives
+/// SIGINT, SIGTERM or SIGHUP.
+pub struct TerminalSession {
+    interrupted: Arc<AtomicBool>,
+    // The signal handlers that set `interrupted`, removed again on drop.
+    signals: Vec<SigId>,
+}
+
+impl TerminalSession {
+    pub fn start() -> io::Result<TerminalSession> {
+        INSTALL_PANIC_HOOK.call_once(|| {
+            let previous = panic::take_hook();
+            panic::set_hook(Box::new(move |info| {
+                restore();
+                previous(info);
+            }));
+        });
+
+        let interrupted = Arc::new(AtomicBool::new(false));
+        let mut signals = vec![];
+        for signal in [
+            signal_hook::consts::SIGINT,
+            signal_hook::consts::SIGTERM,
+            #[cfg(unix)]
+            signal_hook::consts::SIGHUP,
+        ] {
+            match signal_hook::flag::register(signal, Arc::clone(&interrupted)) {
+                Ok(id) => signals.push(id),
+                Err(e) => {
+                    unregister(signals);
+                    return Err(e);
+                }
+            }
+        }
+        // From here on, dropping the session undoes everything.
+        let session = TerminalSession {
+            interrupted,
+            signals,
+        };
+
+        ACTIVE.store(true, Ordering::SeqCst);
+        terminal::enable_raw_mode()?;
+        execute!(stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+
+        // Without this most terminals never report key releases, and keys
+        // are only let go once they are no longer repeated.
+        if terminal::supports_keyboard_enhancement().unwrap_or(false) {
+            execute!(
+                stdout(),
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+            KEYBOARD_ENHANCED.store(true, Ordering::SeqCst);
+        }
+
+        Ok(session)
+    }
+
+    /// Whether the terminal reports key releases, which most only do with
+    /// the keyboard enhancement turned on.
+    pub fn reports_key_releases(&self) -> bool {
+        KEYBOARD_ENHANCED.load(Ordering::SeqCst)
+    }
+
+    /// Whether a signal asked the emulator to quit.
+    pub fn interrupted(&self) -> bool {
+        self.interrupted.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for TerminalSession {
+    fn drop(&mut self) {
+        restore();
+        unregister(std::mem::take(&mut self.signals));
+    }
+}
+
+fn unregister(signals: Vec<SigId>) {
+    for id in signals {
+        signal_hook::low_level::unregister(id);
+    }
+}
+
+fn restore() {
+    if !ACTIVE.swap(false, Ordering::SeqCst) {
+        return;
+    }
+
+    // Errors are ignored, there is nothing better to do with a terminal
+    // that cannot be written to while shutting down.
+    if KEYBOARD_ENHANCED.swap(false, Ordering::SeqCst) {
+        let _ = execute!(stdout(), PopKeyboardEnhancementFlags);
+    }
+    let _ = execute!(stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+}
@@ -1,19 +1,105 @@
+use crate::memory::{Memory, Region, MEMORY_SIZE};
+
+/// Where the VIP interpreter keeps its stack: return addresses are stored
+/// high byte first, growing down from just below this address.
+pub const VIP_STACK_END: u16 = 0xED0;
+
+// The VIP interpreter keeps 0xEA0-0xECF for its stack.
+const VIP_STACK_BYTES: u16 = 48;
+
+pub const DEFAULT_STACK_DEPTH: u16 = 16;
+pub const MAX_STACK_DEPTH: u16 = 255;
+
+/// The return addresses of the subroutines being executed, kept apart from
+/// the program or, like the VIP did, in memory where programs can see them.
 pub struct Stack {
     data: Vec<u16>,
+    limit: usize,
+    in_memory: bool,
 }
 
 impl Stack {
-    pub fn new() -> Stack {
-        Stack { data: vec![] }
+    /// A stack with room for `limit` return addresses.
+    pub fn new(limit: u16, in_memory: bool) -> Stack {
+        Stack {
+            data: Vec::with_capacity(limit as usize),
+            limit: limit as usize,
+            in_memory,
+        }
     }
 
-    pub fn push(&mut self, data: u16) {
-        self.data.push(data);
+    /// Empties the stack. In memory, the addresses stay where they were.
+    pub fn clear(&mut self) {
+        self.data.clear();
     }
 
-    pub fn pop(&mut self) -> u16 {
-        let value = self.data.pop().unwrap();
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Pushes `address`, returning false when the stack is full.
+    pub fn push(&mut self, memory: &mut Memory, address: u16) -> bool {
+        if self.data.len() == self.limit {
+            return false;
+        }
+
+        if self.in_memory {
+            let [high, low] = address.to_be_bytes();
+            let location = Stack::location(self.data.len());
+            memory.set(location, high);
+            memory.set(location + 1, low);
+        }
+        self.data.push(address);
+
+        true
+    }
+
+    /// Pops the last address pushed, or returns `None` when the stack is
+    /// empty. In memory, the address is read back from there, whatever the
+    /// program wrote over it.
+    pub fn pop(&mut self, memory: &Memory) -> Option<u16> {
+        let address = self.data.pop()?;
+
+        if self.in_memory {
+            Some(memory.get_as_u16(Stack::location(self.data.len())))
+        } else {
+            Some(address)
+        }
+    }
+
+    /// The return addresses on the stack, the innermost call first.
+    pub fn return_addresses(&self, memory: &Memory) -> Vec<u16> {
+        (0..self.data.len())
+            .rev()
+            .map(|level| {
+                if self.in_memory {
+                    memory.get_as_u16(Stack::location(level))
+                } else {
+                    self.data[level]
+                }
+            })
+            .collect()
+    }
+
+    /// The memory a stack of `depth` levels kept in memory may use, at least
+    /// the area the VIP keeps for it. Programs may not be loaded there, and
+    /// memory must be 4K or more for it to exist.
+    pub fn memory_region(depth: u16, memory_size: usize) -> Result<Region, String> {
+        if memory_size < MEMORY_SIZE {
+            return Err(format!(
+                "a stack in memory needs at least 4K, it ends at {:#05X}",
+                VIP_STACK_END
+            ));
+        }
+
+        Ok(Region {
+            start: VIP_STACK_END - VIP_STACK_BYTES.max(2 * depth),
+            end: VIP_STACK_END - 1,
+        })
+    }
 
-        value
+    // Where the address at `level` is kept in memory, 0 being the outermost.
+    fn location(level: usize) -> u16 {
+        VIP_STACK_END - 2 * (level as u16 + 1)
     }
 }
@@ -0,0 +1,376 @@
+use std::cmp::Reverse;
+use std::io::{stdout, Write};
+
+use crossterm::{
+    cursor, queue,
+    style::{self, Color, ResetColor, SetBackgroundColor, SetForegroundColor},
+    terminal,
+};
+
+use crate::{
+    display::Display,
+    glyphs::RenderMode,
+    graphics,
+    palette::{to_rgb, ColorDepth, Palette},
+    recording::Cast,
+};
+
+// Rows kept free below the display for the status line.
+const STATUS_ROWS: u16 = 1;
+
+// Assumed size of a character cell in pixels, for terminals that do not
+// report it.
+const DEFAULT_CELL_PIXELS: (u16, u16) = (8, 16);
+
+/// Where and how large the display is drawn in the terminal.
+struct Layout {
+    mode: RenderMode,
+    // Size of a CHIP-8 pixel: in the pixels a character is split into, or in
+    // screen pixels for the graphics modes.
+    scale: u16,
+    left: u16,
+    top: u16,
+    rows: u16,
+}
+
+/// Draws the display into the terminal, centred, either with characters
+/// that each show several pixels or as an image, as chosen by a
+/// `RenderMode`. The terminal itself is set up by a `TerminalSession`.
+pub struct TerminalRenderer {
+    // The colour of each pixel value for images, and reduced to the colours
+    // the terminal supports for characters.
+    image_colors: Vec<[u8; 3]>,
+    cell_colors: Vec<Color>,
+    mode: RenderMode,
+    scale_up: bool,
+    pixel_size: u16,
+    columns: u16,
+    rows: u16,
+    // `None` while the terminal is too small to show the display.
+    layout: Option<Layout>,
+    status: String,
+    message: String,
+    cast: Option<Cast>,
+    // The display last drawn as an image, which is not sent again unchanged.
+    image: Option<Display>,
+}
+
+impl TerminalRenderer {
+    /// The graphics modes draw every CHIP-8 pixel as a square of
+    /// `pixel_size` screen pixels. With `scale_up`, pixels are instead
+    /// enlarged as much as fits. Nothing is drawn until `resize` is called.
+    pub fn new(
+        palette: Palette,
+        mode: RenderMode,
+        scale_up: bool,
+        pixel_size: u16,
+    ) -> TerminalRenderer {
+        TerminalRenderer {
+            image_colors: palette.pixel_colors().into_iter().map(to_rgb).collect(),
+            cell_colors: palette
+                .pixel_colors()
+                .into_iter()
+                .map(|color| ColorDepth::detect().reduce(color))
+                .collect(),
+            mode,
+            scale_up,
+            pixel_size,
+            columns: 0,
+            rows: 0,
+            layout: None,
+            status: String::new(),
+            message: String::new(),
+            cast: None,
+            image: None,
+        }
+    }
+
+    /// Lays the display out for a terminal of `columns` by `rows` cells and
+    /// redraws everything.
+    pub fn resize(&mut self, display: &Display, columns: u16, rows: u16) {
+        let (width, height) = (display.width(), display.height());
+        let mode = self.mode.choose(width, height, STATUS_ROWS, columns, rows);
+
+        // Pixels per cell, as many as the terminal has for images.
+        let (cell_width, cell_height, min_scale) = if mode.is_graphics() {
+            let (cell_width, cell_height) = terminal::window_size()
+                .ok()
+                .filter(|size| size.width > 0 && size.height > 0 && size.columns > 0)
+                .map_or(DEFAULT_CELL_PIXELS, |size| {
+                    (size.width / size.columns, size.height / size.rows)
+                });
+            (cell_width.max(1), cell_height.max(1), self.pixel_size)
+        } else {
+            let (cell_width, cell_height) = mode.cell_size();
+            (cell_width, cell_height, 1)
+        };
+        let used = |scale: u16| {
+            (
+                (width * scale).div_ceil(cell_width),
+                (height * scale).div_ceil(cell_height),
+            )
+        };
+        let fits = |scale: u16| {
+            let (used_columns, used_rows) = used(scale);
+            used_columns <= columns && used_rows + STATUS_ROWS <= rows
+        };
+
+        self.columns = columns;
+        self.rows = rows;
+        self.image = None;
+        self.layout = fits(min_scale).then(|| {
+            let scale = if self.scale_up {
+                (min_scale..)
+                    .take_while(|&scale| fits(scale))
+                    .last()
+                    .unwrap()
+            } else {
+                min_scale
+            };
+            let (used_columns, used_rows) = used(scale);
+
+            Layout {
+                mode,
+                scale,
+                left: (columns - used_columns) / 2,
+                top: (rows - used_rows - STATUS_ROWS) / 2,
+                rows: used_rows,
+            }
+        });
+
+        let mut out = Vec::new();
+        if mode == RenderMode::Kitty {
+            queue!(out, style::Print(graphics::kitty_clear()))
+                .expect("Could not write to the buffer");
+        }
+        queue!(out, terminal::Clear(terminal::ClearType::All))
+            .expect("Could not write to the buffer");
+
+        if self.layout.is_none() {
+            let (used_columns, used_rows) = used(min_scale);
+            queue!(
+                out,
+                cursor::MoveTo(0, 0),
+                style::Print(format!(
+                    "Terminal too small, the screen needs at least {}x{} cells.",
+                    used_columns,
+                    used_rows + STATUS_ROWS
+                ))
+            )
+            .expect("Could not write to the buffer");
+            self.write(&out);
+            self.image = Some(display.clone());
+            return;
+        }
+
+        self.write(&out);
+        self.render(display);
+        self.draw_status();
+        self.draw_message();
+    }
+
+    // The cell at `column` and `row` as the pixels drawn in the foreground,
+    // as bits row by row, and the foreground and background colours. A cell
+    // only has two colours, so when its pixels have more values the most
+    // common lit one becomes the foreground and the most common other one
+    // the background.
+    fn cell(
+        &self,
+        layout: &Layout,
+        display: &Display,
+        column: u16,
+        row: u16,
+    ) -> (u8, Color, Color) {
+        let (cell_width, cell_height) = layout.mode.cell_size();
+        let mut values = Vec::with_capacity((cell_width * cell_height) as usize);
+
+        for dy in 0..cell_height {
+            for dx in 0..cell_width {
+                let x = (column * cell_width + dx) / layout.scale;
+                let y = (row * cell_height + dy) / layout.scale;
+                if x < display.width() && y < display.height() {
+                    values.push(display.get_pixel(x, y));
+                } else {
+                    values.push(0);
+                }
+            }
+        }
+
+        let mut counts = vec![0; self.cell_colors.len()];
+        for &value in &values {
+            counts[value as usize] += 1;
+        }
+        // Ties go to the lower value, so empty cells get the background.
+        let most_common = |value: &u8| (counts[*value as usize], Reverse(*value));
+        let colors = counts.len() as u8;
+        let foreground = (1..colors)
+            .filter(|&value| counts[value as usize] > 0)
+            .max_by_key(most_common)
+            .unwrap_or(1);
+        let background = (0..colors)
+            .filter(|&value| value != foreground)
+            .max_by_key(most_common)
+            .unwrap();
+
+        let bits = values
+            .iter()
+            .enumerate()
+            .filter(|&(_, &value)| value == foreground)
+            .fold(0, |bits, (i, _)| bits | 1 << i);
+
+        (
+            bits,
+            self.cell_colors[foreground as usize],
+            self.cell_colors[background as usize],
+        )
+    }
+
+    /// Shows `text` on the line right below the display.
+    pub fn show_status(&mut self, text: &str) {
+        self.status = text.to_string();
+        self.draw_status();
+    }
+
+    /// Shows `text` in the rows below the status line, replacing what was
+    /// there.
+    pub fn show_message(&mut self, text: &str) {
+        self.message = text.to_string();
+        self.draw_message();
+    }
+
+    fn draw_status(&mut self) {
+        let Some(layout) = &self.layout else {
+            return;
+        };
+        let mut out = Vec::new();
+
+        queue!(
+            out,
+            cursor::MoveTo(layout.left, layout.top + layout.rows),
+            terminal::Clear(terminal::ClearType::CurrentLine),
+            style::Print(self.clipped(layout, &self.status))
+        )
+        .expect("Could not write to the buffer");
+        self.write(&out);
+    }
+
+    fn draw_message(&mut self) {
+        let Some(layout) = &self.layout else {
+            return;
+        };
+        let first_row = layout.top + layout.rows + STATUS_ROWS;
+        let mut out = Vec::new();
+
+        queue!(
+            out,
+            cursor::MoveTo(0, first_row),
+            terminal::Clear(terminal::ClearType::FromCursorDown)
+        )
+        .expect("Could not write to the buffer");
+
+        let rows = first_row..self.rows;
+        for (row, line) in rows.zip(self.message.lines()) {
+            queue!(
+                out,
+                cursor::MoveTo(layout.left, row),
+                style::Print(self.clipped(layout, line))
+            )
+            .expect("Could not write to the buffer");
+        }
+        self.write(&out);
+    }
+
+    // Cuts `text` off at the right edge of the terminal, a line that wraps
+    // would push everything below it down.
+    fn clipped(&self, layout: &Layout, text: &str) -> String {
+        text.chars()
+            .take(self.columns.saturating_sub(layout.left) as usize)
+            .collect()
+    }
+
+    pub fn render(&mut self, display: &Display) {
+        let Some(layout) = &self.layout else {
+            return;
+        };
+
+        if layout.mode.is_graphics() {
+            if self.image.as_ref() == Some(display) {
+                return;
+            }
+            let image = if layout.mode == RenderMode::Kitty {
+                graphics::kitty(display, &self.image_colors, layout.scale)
+            } else {
+                graphics::sixel(display, &self.image_colors, layout.scale)
+            };
+            let mut out = Vec::new();
+            queue!(
+                out,
+                cursor::MoveTo(layout.left, layout.top),
+                style::Print(image)
+            )
+            .expect("Could not write to the buffer");
+            self.write(&out);
+            return;
+        }
+
+        let columns = (display.width() * layout.scale).div_ceil(layout.mode.cell_size().0);
+        let mut out = Vec::new();
+
+        for row in 0..layout.rows {
+            queue!(out, cursor::MoveTo(layout.left, layout.top + row))
+                .expect("Could not write to the buffer");
+
+            // Colours are only sent when they change, which for two-colour
+            // palettes is once per frame.
+            let mut colors = None;
+            for column in 0..columns {
+                let (bits, foreground, background) = self.cell(layout, display, column, row);
+                if colors != Some((foreground, background)) {
+                    queue!(
+                        out,
+                        SetForegroundColor(foreground),
+                        SetBackgroundColor(background)
+                    )
+                    .expect("Could not write to the buffer");
+                    colors = Some((foreground, background));
+                }
+                queue!(out, style::Print(layout.mode.glyph(bits)))
+                    .expect("Could not write to the buffer");
+            }
+        }
+
+        queue!(out, ResetColor).expect("Could not write to the buffer");
+        self.write(&out);
+    }
+
+    /// Starts copying everything drawn into `cast`, beginning with a full
+    /// redraw so the recording shows the whole screen.
+    pub fn start_cast(&mut self, display: &Display, cast: Cast) {
+        self.cast = Some(cast);
+        self.resize(display, self.columns, self.rows);
+    }
+
+    /// Stops copying into the cast started last, if any, and returns it.
+    pub fn stop_cast(&mut self) -> Option<Cast> {
+        self.cast.take()
+    }
+
+    /// Rings the terminal bell between two frames, never inside one.
+    pub fn bell(&mut self) {
+        self.write(b"\x07");
+    }
+
+    pub fn casting(&self) -> bool {
+        self.cast.is_some()
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        let mut out = stdout();
+        out.write_all(bytes).expect("Could not write to stdout");
+        out.flush().expect("Could not flush stdout");
+
+        if let Some(cast) = &mut self.cast {
+            cast.output(bytes);
+        }
+    }
+}
@@ -18,6 +18,6 @@ impl Timer {
     }
 
     pub fn get(&self) -> u8 {
-        return self.value;
+        self.value
     }
 }
@@ -0,0 +1,74 @@
+use clap::ValueEnum;
+
+use crate::opcode::Opcode;
+
+/// Machine cycles of the VIP's 1.76 MHz 1802 in a 60 Hz frame, 8 clock
+/// cycles each.
+pub const VIP_CYCLES_PER_FRAME: i32 = 3668;
+
+/// Cycles of each frame spent in the display interrupt: the routine runs
+/// for all 128 lines the 1861 shows, 14 cycles each, and counts the timers
+/// down.
+pub const VIP_INTERRUPT_CYCLES: i32 = 1832;
+
+// Fetching an instruction and jumping to the routine that executes it.
+const FETCH_CYCLES: u32 = 40;
+
+// What a skip costs on top of the comparison.
+const SKIP_CYCLES: u32 = 4;
+
+/// How much of a program runs in each 60 Hz frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Timing {
+    /// A fixed number of instructions, set with --ipf
+    #[default]
+    Ipf,
+    /// As many as the COSMAC VIP interpreter managed: each instruction
+    /// takes the cycles it took there and drawing waits for the display
+    /// interrupt
+    Vip,
+}
+
+/// The machine cycles the VIP interpreter took for `opcode`, approximated
+/// from its code: `vx` is VX before the instruction and `skipped` whether
+/// it skipped the next one. Instructions the VIP did not have cost no more
+/// than fetching them.
+pub fn vip_cycles(opcode: &Opcode, vx: u8, skipped: bool) -> u32 {
+    let skip = if skipped { SKIP_CYCLES } else { 0 };
+
+    let execute = match (opcode.category, opcode.y, opcode.n) {
+        // Clearing the screen goes through its 256 bytes one at a time.
+        (0x0, 0xE, 0x0) if opcode.x == 0 => 24 + 3078,
+        (0x0, 0xE, 0xE) if opcode.x == 0 => 10,
+        (0x1, ..) => 12,
+        (0x2, ..) => 26,
+        (0x3 | 0x4, ..) => 10 + skip,
+        (0x5 | 0x9, ..) => 18 + skip,
+        (0x6, ..) => 6,
+        (0x7, ..) => 10,
+        (0x8, ..) => 44,
+        (0xA, ..) => 12,
+        (0xB, ..) => 22,
+        (0xC, ..) => 36,
+        // Sprites not on a byte boundary are shifted into two bytes.
+        (0xD, ..) => {
+            let row = if vx.is_multiple_of(8) { 34 } else { 54 };
+            26 + row * opcode.n as u32
+        }
+        (0xE, ..) => 14 + skip,
+        (0xF, 0x0, 0x7) => 10,
+        (0xF, 0x0, 0xA) => 16,
+        (0xF, 0x1, 0x5 | 0x8) => 10,
+        (0xF, 0x1, 0xE) => 16,
+        (0xF, 0x2, 0x9) => 16,
+        // The digits are found by repeated subtraction.
+        (0xF, 0x3, 0x3) => {
+            let digits = vx / 100 + vx / 10 % 10 + vx % 10;
+            84 + 16 * digits as u32
+        }
+        (0xF, 0x5 | 0x6, 0x5) => 14 + 14 * (opcode.x as u32 + 1),
+        _ => 0,
+    };
+
+    FETCH_CYCLES + execute
+}
@@ -0,0 +1,367 @@
+use crate::{
+    cdp1802::{Bus, Cdp1802},
+    display::Display,
+    keypad::Key,
+    machine::Machine,
+    memory::MemoryMap,
+    processor::Fault,
+    stack::VIP_STACK_END,
+};
+
+/// RAM ends where A15 selects the ROM.
+pub const MAX_VIP_RAM: usize = 0x8000;
+
+// The 1861 draws 262 lines a frame, each taking 14 machine cycles. On the
+// 128 lines of the picture it takes 8 of them to fetch a line of 8 bytes by
+// DMA, after the CPU had the first 6.
+const LINE_CYCLES: u32 = 14;
+const FRAME_CYCLES: u32 = 262 * LINE_CYCLES;
+const FIRST_DISPLAY_LINE: u32 = 80;
+const DISPLAY_LINES: u32 = 128;
+const DMA_START: u32 = 6;
+
+// The interrupt comes 29 cycles before the first DMA transfer, for the
+// interrupt routine to get ready.
+const FIRST_DMA_CYCLE: u32 = FIRST_DISPLAY_LINE * LINE_CYCLES + DMA_START;
+const INTERRUPT_CYCLE: u32 = FIRST_DMA_CYCLE - 29;
+
+// EF1 is asserted on the 4 lines before the picture and its last 4.
+const EF1_LINES: [std::ops::Range<u32>; 2] = [
+    FIRST_DISPLAY_LINE - 4..FIRST_DISPLAY_LINE,
+    FIRST_DISPLAY_LINE + DISPLAY_LINES - 4..FIRST_DISPLAY_LINE + DISPLAY_LINES,
+];
+
+/// A COSMAC VIP: an RCA 1802 with RAM from 0000 and the operating system
+/// ROM from 8000, a CDP1861 video chip reading the picture by DMA and a hex
+/// keypad. It runs the original CHIP-8 interpreter, loaded at 0000 with the
+/// program after it.
+pub struct Vip {
+    cpu: Cdp1802,
+    board: Board,
+    interpreter: Vec<u8>,
+    program_address: u16,
+    initial_ram: Vec<u8>,
+    // Machine cycles into the current frame.
+    cycle: u32,
+    // The next line of the picture to fetch.
+    line: u32,
+    interrupted: bool,
+    instructions: u64,
+    // Whether Q was on in this frame and in the last one.
+    beeping: bool,
+    buzzing: bool,
+    redraw: bool,
+}
+
+/// What the 1802 is wired to.
+struct Board {
+    ram: Vec<u8>,
+    rom: Vec<u8>,
+    // After a reset the ROM is read at 0000 as well, until an address with
+    // A15 set is read.
+    rom_at_zero: bool,
+    keys: [bool; 16],
+    // The key OUT 2 selected, which EF3 tells is held or not.
+    key_latch: u8,
+    display_on: bool,
+    ef1: bool,
+    display: Display,
+    changed: bool,
+}
+
+impl Vip {
+    /// A VIP with `interpreter` loaded at 0000 and `program` where `map`
+    /// says, with the RAM size of `map`. The operating system `rom` starts
+    /// the interpreter and holds its display interrupt routine and font.
+    pub fn new(
+        interpreter: &[u8],
+        rom: Vec<u8>,
+        map: &MemoryMap,
+        program: &[u8],
+    ) -> Result<Vip, String> {
+        if map.size > MAX_VIP_RAM {
+            return Err(format!(
+                "the VIP has at most {}K of RAM",
+                MAX_VIP_RAM / 1024
+            ));
+        }
+        if interpreter.len() > map.program_address as usize {
+            return Err(format!(
+                "the interpreter is {} bytes and would overlap the program at {:#05X}",
+                interpreter.len(),
+                map.program_address
+            ));
+        }
+        if rom.is_empty() || rom.len() > 0x8000 {
+            return Err("the VIP ROM must be from 1 byte to 32K long".into());
+        }
+
+        let mut vip = Vip {
+            cpu: Cdp1802::new(),
+            board: Board {
+                ram: vec![],
+                rom,
+                rom_at_zero: false,
+                keys: [false; 16],
+                key_latch: 0,
+                display_on: false,
+                ef1: false,
+                display: Display::new(64, 32),
+                changed: false,
+            },
+            interpreter: interpreter.to_vec(),
+            program_address: map.program_address,
+            initial_ram: vec![0; map.size],
+            cycle: 0,
+            line: 0,
+            interrupted: false,
+            instructions: 0,
+            beeping: false,
+            buzzing: false,
+            redraw: false,
+        };
+        vip.load_program(program)?;
+
+        Ok(vip)
+    }
+
+    // The end of the RAM the ROM finds, which only looks as far as 4K. The
+    // interpreter keeps its stack, variables and display just below it.
+    fn ram_top(&self) -> u16 {
+        self.initial_ram.len().min(0x1000) as u16
+    }
+
+    // Fetches one line of the picture. The interpreter has R0 go over each
+    // row of its 64x32 display four times, the last time is shown.
+    fn dma(&mut self) {
+        let y = (self.line / 4) as u16;
+
+        for byte in 0..8 {
+            let value = self.cpu.dma_out(&mut self.board);
+            for bit in 0..8 {
+                let x = byte * 8 + bit;
+                let pixel = (value >> (7 - bit)) & 1;
+                if self.board.display.get_pixel(x, y) != pixel {
+                    self.board.display.set_pixel(x, y, pixel);
+                    self.board.changed = true;
+                }
+            }
+        }
+
+        self.cycle += 8;
+    }
+}
+
+impl Machine for Vip {
+    fn display(&self) -> &Display {
+        &self.board.display
+    }
+
+    fn take_redraw(&mut self) -> bool {
+        std::mem::take(&mut self.redraw)
+    }
+
+    fn frame_finished(&self) -> bool {
+        self.cycle >= FRAME_CYCLES
+    }
+
+    /// Runs the CPU for one instruction, or the 1861 for one interrupt or
+    /// line of DMA when it is time for that.
+    fn emulate_cycle(&mut self) -> Result<(), Fault> {
+        let line = self.cycle / LINE_CYCLES;
+        self.board.ef1 = EF1_LINES.iter().any(|lines| lines.contains(&line));
+
+        // The interrupt request lasts until the picture starts, and is only
+        // taken while interrupts are enabled.
+        if self.board.display_on
+            && !self.interrupted
+            && (INTERRUPT_CYCLE..FIRST_DMA_CYCLE).contains(&self.cycle)
+        {
+            let cycles = self.cpu.interrupt();
+            if cycles > 0 {
+                self.interrupted = true;
+                self.cycle += cycles;
+                return Ok(());
+            }
+        }
+
+        let dma_cycle = FIRST_DMA_CYCLE + self.line * LINE_CYCLES;
+        if self.line < DISPLAY_LINES && self.cycle >= dma_cycle {
+            if self.board.display_on {
+                self.dma();
+            }
+            self.line += 1;
+            if self.line == DISPLAY_LINES && std::mem::take(&mut self.board.changed) {
+                self.redraw = true;
+            }
+            return Ok(());
+        }
+
+        let (cycles, executed) = self.cpu.step(&mut self.board);
+        self.cycle += cycles;
+        self.instructions += executed as u64;
+        self.beeping |= self.cpu.q();
+
+        Ok(())
+    }
+
+    fn frame_position(&self) -> Option<f64> {
+        Some((self.cycle as f64 / FRAME_CYCLES as f64).min(1.0))
+    }
+
+    /// Marks the end of a frame. A frame ended early, e.g. by a caller
+    /// stopping mid-frame, starts the next one from the beginning.
+    fn end_frame(&mut self) {
+        self.cycle = self.cycle.saturating_sub(FRAME_CYCLES);
+        self.line = 0;
+        self.interrupted = false;
+        self.buzzing = self.beeping;
+        self.beeping = self.cpu.q();
+    }
+
+    /// Instructions of the 1802 executed since the machine was created.
+    fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    fn buzzing(&self) -> bool {
+        self.buzzing
+    }
+
+    /// Whether Q, which drives the speaker, is on.
+    fn sound_active(&self) -> bool {
+        self.cpu.q()
+    }
+
+    fn handle_key_down(&mut self, key: Key) {
+        self.board.keys[key as usize] = true;
+    }
+
+    fn handle_key_up(&mut self, key: Key) {
+        self.board.keys[key as usize] = false;
+    }
+
+    fn held_keys(&self) -> Vec<u8> {
+        (0..16)
+            .filter(|&key| self.board.keys[key as usize])
+            .collect()
+    }
+
+    /// Resets the VIP with the memory as it was loaded, for the ROM to
+    /// start the interpreter again.
+    fn reset(&mut self) {
+        self.cpu = Cdp1802::new();
+        self.board.ram = self.initial_ram.clone();
+        self.board.rom_at_zero = true;
+        self.board.display_on = false;
+        self.board.display.clear();
+        self.cycle = 0;
+        self.line = 0;
+        self.interrupted = false;
+        self.beeping = false;
+        self.buzzing = false;
+        self.redraw = true;
+    }
+
+    fn load_program(&mut self, program: &[u8]) -> Result<(), String> {
+        let start = self.program_address as usize;
+        let end = start + program.len();
+        if end > self.initial_ram.len() {
+            return Err(format!(
+                "{} bytes from {:#05X} do not fit in {} bytes of RAM",
+                program.len(),
+                start,
+                self.initial_ram.len()
+            ));
+        }
+
+        self.initial_ram.fill(0);
+        self.initial_ram[..self.interpreter.len()].copy_from_slice(&self.interpreter);
+        self.initial_ram[start..end].copy_from_slice(program);
+        self.reset();
+
+        Ok(())
+    }
+
+    /// R5, which the interpreter keeps the program counter in.
+    fn program_counter(&self) -> u16 {
+        self.cpu.register(5)
+    }
+
+    /// The return addresses the interpreter pushed from R2 up to the end of
+    /// its stack, which it puts at 0xED0 with 4K of RAM and 0x6D0 with 2K.
+    fn call_stack(&self) -> Vec<u16> {
+        let end = VIP_STACK_END - (0x1000 - self.ram_top());
+        let pointer = self.cpu.register(2);
+        if !(end - 0x100..end).contains(&pointer) {
+            return vec![];
+        }
+
+        (pointer + 1..end - 1)
+            .step_by(2)
+            .map(|address| {
+                let read = |address: u16| self.board.ram[address as usize % self.board.ram.len()];
+                u16::from_be_bytes([read(address), read(address + 1)])
+            })
+            .collect()
+    }
+
+    fn instructions_per_frame(&self) -> Option<u32> {
+        None
+    }
+
+    fn set_instructions_per_frame(&mut self, _instructions_per_frame: u32) {}
+
+    fn pacing(&self) -> String {
+        "COSMAC VIP".to_string()
+    }
+}
+
+impl Bus for Board {
+    fn read(&mut self, address: u16) -> u8 {
+        if address & 0x8000 != 0 {
+            self.rom_at_zero = false;
+        }
+
+        if address & 0x8000 != 0 || self.rom_at_zero {
+            self.rom[address as usize % self.rom.len()]
+        } else {
+            self.ram[address as usize % self.ram.len()]
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        if address & 0x8000 == 0 {
+            let size = self.ram.len();
+            self.ram[address as usize % size] = value;
+        }
+    }
+
+    /// OUT 1 turns the 1861 off, OUT 2 selects a key.
+    fn output(&mut self, port: u8, value: u8) {
+        match port {
+            1 => self.display_on = false,
+            2 => self.key_latch = value & 0xF,
+            _ => {}
+        }
+    }
+
+    /// INP 1 turns the 1861 on.
+    fn input(&mut self, port: u8) -> u8 {
+        if port == 1 {
+            self.display_on = true;
+        }
+
+        0
+    }
+
+    /// EF1 is the 1861's signal that the picture starts or ends soon, EF3
+    /// is asserted while the selected key is held.
+    fn flag(&self, line: u8) -> bool {
+        match line {
+            1 => self.ef1,
+            3 => self.keys[self.key_latch as usize],
+            _ => false,
+        }
+    }
+}